@@ -0,0 +1,386 @@
+//! Declarative response assertions from `# @assert` directives, e.g.
+//! `status == 201`, `header Content-Type contains json`,
+//! `jsonpath $.items.length() > 0`, `duration < 500ms` or `timing ttfb < 200ms`.
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::client::HttpResponse;
+use crate::jsonpath;
+use crate::script::TestResult;
+use crate::timing;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subject {
+    Status,
+    Header(String),
+    JsonPath(String),
+    Body,
+    /// Response time in milliseconds
+    Duration,
+    /// A phase of `Timings` in milliseconds, e.g. `ttfb`
+    Timing(String),
+}
+
+impl Subject {
+    /// Whether values are durations written like `500ms` or `2s`.
+    fn is_duration(&self) -> bool {
+        matches!(self, Subject::Duration | Subject::Timing(_))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    Matches,
+    Exists,
+}
+
+impl Operator {
+    fn parse(token: &str) -> Option<Self> {
+        Some(match token {
+            "==" => Operator::Eq,
+            "!=" => Operator::Ne,
+            "<" => Operator::Lt,
+            "<=" => Operator::Le,
+            ">" => Operator::Gt,
+            ">=" => Operator::Ge,
+            "contains" => Operator::Contains,
+            "matches" => Operator::Matches,
+            "exists" => Operator::Exists,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assertion {
+    pub subject: Subject,
+    pub operator: Operator,
+    pub expected: Option<String>,
+    /// The assertion as written, used when reporting results
+    pub text: String,
+}
+
+impl Assertion {
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let (subject_token, rest) = split_token(text);
+
+        let (subject, rest) = match subject_token {
+            "status" => (Subject::Status, rest),
+            "body" => (Subject::Body, rest),
+            "duration" => (Subject::Duration, rest),
+            "timing" => {
+                let (phase, rest) = split_token(rest);
+                if !timing::PHASES.contains(&phase) {
+                    bail!("Unknown timing '{}' in assertion, expected one of {}: {}", phase, timing::PHASES.join(", "), text);
+                }
+                (Subject::Timing(phase.to_string()), rest)
+            }
+            "header" | "jsonpath" => {
+                let (arg, rest) = split_token(rest);
+                if arg.is_empty() {
+                    bail!("Missing {} argument in assertion: {}", subject_token, text);
+                }
+                let subject = if subject_token == "header" {
+                    Subject::Header(arg.to_string())
+                } else {
+                    Subject::JsonPath(arg.to_string())
+                };
+                (subject, rest)
+            }
+            "" => bail!("Empty assertion"),
+            other => bail!("Unknown assertion subject '{}' in: {}", other, text),
+        };
+
+        let (op_token, rest) = split_token(rest);
+        let operator = Operator::parse(op_token)
+            .with_context(|| format!("Unknown assertion operator '{}' in: {}", op_token, text))?;
+
+        let expected = unquote(rest.trim());
+        let expected = match (operator, expected.is_empty()) {
+            (Operator::Exists, true) => None,
+            (Operator::Exists, false) => bail!("'exists' takes no value in assertion: {}", text),
+            (_, true) => bail!("Missing expected value in assertion: {}", text),
+            (_, false) => Some(expected.to_string()),
+        };
+
+        if subject.is_duration() {
+            if let Some(value) = &expected {
+                parse_duration_ms(value)?;
+            }
+        }
+
+        Ok(Self {
+            subject,
+            operator,
+            expected,
+            text: text.to_string(),
+        })
+    }
+
+    pub fn check(&self, response: &HttpResponse) -> TestResult {
+        let name = format!("@assert {}", self.text);
+        match self.evaluate(response) {
+            Ok(()) => TestResult { name, passed: true, message: None },
+            Err(e) => TestResult { name, passed: false, message: Some(e.to_string()) },
+        }
+    }
+
+    fn evaluate(&self, response: &HttpResponse) -> Result<()> {
+        let actual = self.actual(response)?;
+
+        if self.operator == Operator::Exists {
+            return match actual {
+                Some(_) => Ok(()),
+                None => bail!("{} does not exist", self.subject_label()),
+            };
+        }
+
+        let Some(actual) = actual else {
+            bail!("{} does not exist", self.subject_label());
+        };
+        let expected = self.expected_value()?;
+
+        if compare(&actual, self.operator, &expected)? {
+            Ok(())
+        } else {
+            bail!("{} was {}", self.subject_label(), text(&actual))
+        }
+    }
+
+    fn actual(&self, response: &HttpResponse) -> Result<Option<Value>> {
+        Ok(match &self.subject {
+            Subject::Status => Some(Value::from(response.status)),
+            Subject::Header(name) => response.headers.get(name).map(Value::from),
+            Subject::JsonPath(path) => {
+                let json: Value = serde_json::from_str(response.checked_text()?)
+                    .context("Response body is not valid JSON")?;
+                jsonpath::select_one(&json, path)?
+            }
+            Subject::Body => Some(Value::String(response.checked_text()?.to_string())),
+            Subject::Duration => Some(Value::from(response.duration.as_millis() as u64)),
+            Subject::Timing(phase) => response
+                .timings
+                .phase(phase)
+                .map(|duration| Value::from(duration.as_millis() as u64)),
+        })
+    }
+
+    fn expected_value(&self) -> Result<Value> {
+        let expected = self.expected.as_deref().unwrap_or_default();
+        if self.subject.is_duration() {
+            return Ok(Value::from(parse_duration_ms(expected)?));
+        }
+        Ok(serde_json::from_str(expected).unwrap_or_else(|_| Value::String(expected.to_string())))
+    }
+
+    fn subject_label(&self) -> String {
+        match &self.subject {
+            Subject::Status => "status".to_string(),
+            Subject::Header(name) => format!("header {}", name),
+            Subject::JsonPath(path) => path.clone(),
+            Subject::Body => "body".to_string(),
+            Subject::Duration => "duration (ms)".to_string(),
+            Subject::Timing(phase) => format!("timing {} (ms)", phase),
+        }
+    }
+}
+
+fn compare(actual: &Value, operator: Operator, expected: &Value) -> Result<bool> {
+    Ok(match operator {
+        Operator::Eq => equals(actual, expected),
+        Operator::Ne => !equals(actual, expected),
+        Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge => {
+            let (Some(a), Some(e)) = (number(actual), number(expected)) else {
+                bail!("cannot compare {} with {} numerically", text(actual), text(expected));
+            };
+            match operator {
+                Operator::Lt => a < e,
+                Operator::Le => a <= e,
+                Operator::Gt => a > e,
+                _ => a >= e,
+            }
+        }
+        Operator::Contains => match actual {
+            Value::Array(items) => items.iter().any(|item| equals(item, expected)),
+            _ => text(actual).contains(&text(expected)),
+        },
+        Operator::Matches => {
+            let re = Regex::new(&text(expected))
+                .with_context(|| format!("Invalid regular expression: {}", text(expected)))?;
+            re.is_match(&text(actual))
+        }
+        Operator::Exists => true,
+    })
+}
+
+fn equals(actual: &Value, expected: &Value) -> bool {
+    if actual == expected {
+        return true;
+    }
+    match (number(actual), number(expected)) {
+        (Some(a), Some(e)) => a == e,
+        _ => text(actual) == text(expected),
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Parses `500ms`, `2s`, `1.5s` or a bare number of milliseconds.
+fn parse_duration_ms(value: &str) -> Result<f64> {
+    let value = value.trim();
+    let (number, scale) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 1.0)
+    } else if let Some(s) = value.strip_suffix('s') {
+        (s, 1000.0)
+    } else {
+        (value, 1.0)
+    };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid duration: {}", value))?;
+    Ok(number * scale)
+}
+
+fn split_token(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(pos) => (&s[..pos], &s[pos..]),
+        None => (s, ""),
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .unwrap_or(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::ResponseBody;
+    use crate::headers::Headers;
+    use crate::timing::Timings;
+    use std::time::Duration;
+
+    fn response() -> HttpResponse {
+        HttpResponse {
+            request: Default::default(),
+            status: 201,
+            version: reqwest::Version::HTTP_11,
+            redirects: Vec::new(),
+            saved_to: None,
+            timings: Timings {
+                connect: Some(Duration::from_millis(20)),
+                ttfb: Duration::from_millis(90),
+                ..Timings::default()
+            },
+            headers: Headers::from([("Content-Type".to_string(), "application/json".to_string())]),
+            body: ResponseBody::from(r#"{"id": "42", "items": [{"name": "a"}, {"name": "b"}]}"#),
+            content_type: Some("application/json".to_string()),
+            duration: Duration::from_millis(120),
+            tests: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    fn check(text: &str) -> TestResult {
+        Assertion::parse(text).unwrap().check(&response())
+    }
+
+    #[test]
+    fn test_parse_assertion() {
+        let assertion = Assertion::parse("header Content-Type contains json").unwrap();
+        assert_eq!(assertion.subject, Subject::Header("Content-Type".to_string()));
+        assert_eq!(assertion.operator, Operator::Contains);
+        assert_eq!(assertion.expected.as_deref(), Some("json"));
+    }
+
+    #[test]
+    fn test_parse_invalid_assertions() {
+        assert!(Assertion::parse("").is_err());
+        assert!(Assertion::parse("latency < 5").is_err());
+        assert!(Assertion::parse("status ~= 200").is_err());
+        assert!(Assertion::parse("status ==").is_err());
+        assert!(Assertion::parse("header").is_err());
+        assert!(Assertion::parse("duration < fast").is_err());
+        assert!(Assertion::parse("timing latency < 5ms").is_err());
+        assert!(Assertion::parse("timing ttfb < fast").is_err());
+    }
+
+    #[test]
+    fn test_status_assertions() {
+        assert!(check("status == 201").passed);
+        assert!(check("status >= 200").passed);
+        assert!(!check("status == 200").passed);
+        assert_eq!(check("status == 200").message.as_deref(), Some("status was 201"));
+    }
+
+    #[test]
+    fn test_header_assertions() {
+        assert!(check("header content-type contains json").passed);
+        assert!(check("header Content-Type == application/json").passed);
+        assert!(check("header Content-Type exists").passed);
+        assert!(!check("header Location exists").passed);
+    }
+
+    #[test]
+    fn test_jsonpath_assertions() {
+        assert!(check("jsonpath $.items.length() > 0").passed);
+        assert!(check("jsonpath $.id == 42").passed);
+        assert!(check("jsonpath $.id == \"42\"").passed);
+        assert!(check("jsonpath $.items[*].name contains \"b\"").passed);
+        assert!(!check("jsonpath $.missing exists").passed);
+    }
+
+    #[test]
+    fn test_body_and_duration_assertions() {
+        assert!(check("body contains items").passed);
+        assert!(check("body matches \"id\":\\s*\"\\d+\"").passed);
+        assert!(check("duration < 500ms").passed);
+        assert!(check("duration < 1s").passed);
+        assert!(!check("duration < 100").passed);
+    }
+
+    #[test]
+    fn test_timing_assertions() {
+        assert!(check("timing ttfb < 100ms").passed);
+        assert!(check("timing dns == 0").passed);
+        assert!(check("timing tls == 0").passed);
+        assert!(check("timing connect < 50ms").passed);
+        let result = check("timing ttfb < 50ms");
+        assert_eq!(result.message.as_deref(), Some("timing ttfb (ms) was 90"));
+    }
+
+    #[test]
+    fn test_jsonpath_on_non_json_body() {
+        let mut response = response();
+        response.body = ResponseBody::from("plain");
+        let result = Assertion::parse("jsonpath $.id exists").unwrap().check(&response);
+        assert!(!result.passed);
+    }
+}
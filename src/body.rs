@@ -0,0 +1,388 @@
+//! Response bodies: the raw bytes as received, decompressed according to
+//! `Content-Encoding`, with a text view decoded from the declared charset.
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use encoding_rs::{Encoding, UTF_8};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Encodings that `decode_content` understands, sent as `Accept-Encoding`
+pub const ACCEPT_ENCODING: &str = "gzip, deflate, br, zstd";
+
+/// Bytes shown in the hex summary of a binary body
+const SUMMARY_BYTES: usize = 64;
+
+#[derive(Debug, Clone, Default)]
+pub struct ResponseBody {
+    bytes: Bytes,
+    /// `Content-Encoding` of the response, if any
+    content_encoding: Option<String>,
+    /// Whether `bytes` were decompressed from `content_encoding`
+    decoded: bool,
+    /// Size of the body as received
+    encoded_len: usize,
+    /// Set when the body was saved to a file and too large to also keep in
+    /// memory, leaving `bytes` empty
+    omitted: bool,
+    charset: Option<&'static Encoding>,
+    text: OnceLock<String>,
+}
+
+impl ResponseBody {
+    /// Creates a body from the received bytes. With `decompress`, bodies in a
+    /// supported `content_encoding` are decompressed; others are kept as is.
+    pub fn new(
+        received: Bytes,
+        content_type: Option<&str>,
+        content_encoding: Option<&str>,
+        decompress: bool,
+    ) -> Result<Self> {
+        let encoded_len = received.len();
+        let content_encoding = content_encoding
+            .map(str::trim)
+            .filter(|e| !e.is_empty() && !e.eq_ignore_ascii_case("identity"))
+            .map(str::to_string);
+
+        let (bytes, decoded) = match &content_encoding {
+            Some(encoding) if decompress && is_supported(encoding) => (Bytes::from(decode_content(&received, encoding)?), true),
+            _ => (received, false),
+        };
+
+        Ok(Self {
+            bytes,
+            content_encoding,
+            decoded,
+            encoded_len,
+            omitted: false,
+            charset: content_type.and_then(charset),
+            text: OnceLock::new(),
+        })
+    }
+
+    /// Creates a body from bytes that were already decompressed while
+    /// streaming, e.g. to a file. `None` if they were too large to keep.
+    pub(crate) fn streamed(
+        bytes: Option<Bytes>,
+        content_type: Option<&str>,
+        content_encoding: Option<&str>,
+        decoded: bool,
+        encoded_len: usize,
+    ) -> Self {
+        Self {
+            omitted: bytes.is_none(),
+            bytes: bytes.unwrap_or_default(),
+            content_encoding: content_encoding.map(str::to_string),
+            decoded,
+            encoded_len,
+            charset: content_type.and_then(charset),
+            text: OnceLock::new(),
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn content_encoding(&self) -> Option<&str> {
+        self.content_encoding.as_deref()
+    }
+
+    pub fn is_decoded(&self) -> bool {
+        self.decoded
+    }
+
+    /// Whether the body was saved to a file without being kept in memory.
+    pub fn is_omitted(&self) -> bool {
+        self.omitted
+    }
+
+    /// Size of the body as received, before decompression.
+    pub fn encoded_len(&self) -> usize {
+        self.encoded_len
+    }
+
+    /// The body decoded with its charset (UTF-8 unless declared or given by
+    /// a byte order mark). Decoded on first use.
+    pub fn text(&self) -> &str {
+        self.text.get_or_init(|| {
+            let (text, _, _) = self.charset.unwrap_or(UTF_8).decode(&self.bytes);
+            text.into_owned()
+        })
+    }
+
+    /// Whether the body is not text: it contains NUL bytes, is not valid in
+    /// its charset, or is mostly control characters.
+    pub fn is_binary(&self) -> bool {
+        if self.bytes.contains(&0) {
+            return true;
+        }
+        let encoding = Encoding::for_bom(&self.bytes)
+            .map(|(encoding, _)| encoding)
+            .or(self.charset)
+            .unwrap_or(UTF_8);
+        let (text, malformed) = encoding.decode_with_bom_removal(&self.bytes);
+        if malformed {
+            return true;
+        }
+        let control = text
+            .chars()
+            .filter(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c' | '\x1b'))
+            .count();
+        control * 10 > text.chars().count()
+    }
+
+    /// A size line and a hex dump of the first bytes, shown for binary bodies.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("<binary body, {} bytes>", self.len());
+        for (index, chunk) in self.bytes[..self.len().min(SUMMARY_BYTES)].chunks(16).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            summary.push_str(&format!("\n{:08x}  {:<47}  {}", index * 16, hex.join(" "), ascii));
+        }
+        if self.len() > SUMMARY_BYTES {
+            summary.push_str("\n...");
+        }
+        summary
+    }
+}
+
+impl From<&str> for ResponseBody {
+    fn from(text: &str) -> Self {
+        Self::from(text.to_string())
+    }
+}
+
+impl From<String> for ResponseBody {
+    fn from(text: String) -> Self {
+        let bytes = Bytes::from(text.clone());
+        Self {
+            encoded_len: bytes.len(),
+            bytes,
+            text: OnceLock::from(text),
+            ..Self::default()
+        }
+    }
+}
+
+/// The `charset` parameter of a content type, if it names a known encoding.
+fn charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| Encoding::for_label(value.trim().trim_matches('"').as_bytes()))
+            .flatten()
+    })
+}
+
+pub(crate) fn is_supported(content_encoding: &str) -> bool {
+    content_encoding.split(',').all(|encoding| {
+        matches!(
+            encoding.trim().to_lowercase().as_str(),
+            "gzip" | "x-gzip" | "deflate" | "br" | "zstd" | "identity"
+        )
+    })
+}
+
+/// Decompresses `bytes` encoded with `content_encoding`, which may list
+/// several encodings in the order they were applied.
+pub fn decode_content(bytes: &[u8], content_encoding: &str) -> Result<Vec<u8>> {
+    let mut data = bytes.to_vec();
+    for encoding in content_encoding.split(',').rev() {
+        let encoding = encoding.trim().to_lowercase();
+        let mut decoded = Vec::new();
+        match encoding.as_str() {
+            "gzip" | "x-gzip" => {
+                flate2::read::MultiGzDecoder::new(data.as_slice()).read_to_end(&mut decoded)?;
+            }
+            "deflate" => {
+                // Servers send both zlib-wrapped and raw deflate data
+                if flate2::read::ZlibDecoder::new(data.as_slice()).read_to_end(&mut decoded).is_err() {
+                    decoded.clear();
+                    flate2::read::DeflateDecoder::new(data.as_slice()).read_to_end(&mut decoded)?;
+                }
+            }
+            "br" => {
+                brotli::Decompressor::new(data.as_slice(), 4096).read_to_end(&mut decoded)?;
+            }
+            "zstd" => decoded = zstd::decode_all(data.as_slice())?,
+            "identity" => continue,
+            _ => bail!("Unsupported Content-Encoding: {}", encoding),
+        }
+        data = decoded;
+    }
+    Ok(data)
+}
+
+/// Collects what a chain of decoders wrote, so it can be taken after each chunk.
+#[derive(Clone, Default)]
+struct Sink(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decompresses a body chunk by chunk, so it can be written out without
+/// holding all of it in memory.
+pub(crate) struct StreamDecoder {
+    writer: Box<dyn Write + Send>,
+    output: Sink,
+}
+
+impl StreamDecoder {
+    /// Creates a decoder for `content_encoding`; `None` passes chunks through.
+    pub fn new(content_encoding: Option<&str>) -> Result<Self> {
+        let output = Sink::default();
+        let mut writer: Box<dyn Write + Send> = Box::new(output.clone());
+        // The last applied encoding is decoded first, so it wraps the others
+        for encoding in content_encoding.unwrap_or_default().split(',') {
+            let encoding = encoding.trim().to_lowercase();
+            writer = match encoding.as_str() {
+                "" | "identity" => writer,
+                "gzip" | "x-gzip" => Box::new(flate2::write::MultiGzDecoder::new(writer)),
+                "deflate" => Box::new(flate2::write::ZlibDecoder::new(writer)),
+                "br" => Box::new(brotli::DecompressorWriter::new(writer, 4096)),
+                "zstd" => Box::new(zstd::stream::write::Decoder::new(writer)?.auto_flush()),
+                _ => bail!("Unsupported Content-Encoding: {}", encoding),
+            };
+        }
+        Ok(Self { writer, output })
+    }
+
+    /// Decodes `chunk`, returning the output available so far.
+    pub fn write(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        self.writer.write_all(chunk)?;
+        Ok(std::mem::take(&mut *self.output.0.lock().unwrap()))
+    }
+
+    /// Flushes the decoders and returns the remaining output.
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        self.writer.flush()?;
+        drop(self.writer);
+        Ok(std::mem::take(&mut *self.output.0.lock().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_decode_content() {
+        let text = b"hello hello hello hello";
+        assert_eq!(decode_content(&gzip(text), "gzip").unwrap(), text);
+
+        let mut brotli_data = Vec::new();
+        brotli::CompressorWriter::new(&mut brotli_data, 4096, 5, 22).write_all(text).unwrap();
+        assert_eq!(decode_content(&brotli_data, "br").unwrap(), text);
+
+        let zstd_data = zstd::encode_all(&text[..], 3).unwrap();
+        assert_eq!(decode_content(&zstd_data, "zstd").unwrap(), text);
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(text).unwrap();
+        assert_eq!(decode_content(&zlib.finish().unwrap(), "deflate").unwrap(), text);
+
+        // Applied in order: gzip first, then zstd
+        let layered = zstd::encode_all(gzip(text).as_slice(), 3).unwrap();
+        assert_eq!(decode_content(&layered, "gzip, zstd").unwrap(), text);
+
+        assert!(decode_content(text, "gzip").is_err());
+        assert!(decode_content(text, "compress").is_err());
+    }
+
+    #[test]
+    fn test_stream_decoder() {
+        let text: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+        let layered = zstd::encode_all(gzip(&text).as_slice(), 3).unwrap();
+
+        let mut decoder = StreamDecoder::new(Some("gzip, zstd")).unwrap();
+        let mut output = Vec::new();
+        for chunk in layered.chunks(100) {
+            output.extend(decoder.write(chunk).unwrap());
+        }
+        output.extend(decoder.finish().unwrap());
+        assert_eq!(output, text);
+
+        let mut passthrough = StreamDecoder::new(None).unwrap();
+        assert_eq!(passthrough.write(b"raw").unwrap(), b"raw");
+        assert!(StreamDecoder::new(Some("compress")).is_err());
+    }
+
+    #[test]
+    fn test_decompression_is_optional() {
+        let compressed = Bytes::from(gzip(b"{\"ok\": true}"));
+
+        let body = ResponseBody::new(compressed.clone(), None, Some("gzip"), true).unwrap();
+        assert_eq!(body.text(), "{\"ok\": true}");
+        assert!(body.is_decoded());
+        assert_eq!(body.encoded_len(), compressed.len());
+
+        let body = ResponseBody::new(compressed.clone(), None, Some("gzip"), false).unwrap();
+        assert_eq!(body.bytes(), &compressed[..]);
+        assert!(!body.is_decoded());
+        assert!(body.is_binary());
+
+        // Unknown encodings are left alone rather than failing the request
+        let body = ResponseBody::new(Bytes::from_static(b"abc"), None, Some("compress"), true).unwrap();
+        assert_eq!((body.text(), body.content_encoding()), ("abc", Some("compress")));
+    }
+
+    #[test]
+    fn test_text_honours_charset() {
+        let latin1 = Bytes::from_static(b"caf\xe9");
+        let body = ResponseBody::new(latin1.clone(), Some("text/plain; charset=ISO-8859-1"), None, true).unwrap();
+        assert_eq!(body.text(), "café");
+        assert!(!body.is_binary());
+
+        let body = ResponseBody::new(latin1, Some("text/plain"), None, true).unwrap();
+        assert_eq!(body.text(), "caf\u{fffd}");
+        assert!(body.is_binary());
+
+        let utf16 = Bytes::from_static(b"\xff\xfeh\x00i\x00");
+        let body = ResponseBody::new(utf16, None, None, true).unwrap();
+        assert_eq!(body.text(), "hi");
+    }
+
+    #[test]
+    fn test_binary_summary() {
+        let png = Bytes::from_static(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x00\x10");
+        let body = ResponseBody::new(png, Some("image/png"), None, true).unwrap();
+        assert!(body.is_binary());
+        assert_eq!(
+            body.summary(),
+            "<binary body, 20 bytes>\n\
+             00000000  89 50 4e 47 0d 0a 1a 0a 00 00 00 0d 49 48 44 52  .PNG........IHDR\n\
+             00000010  00 00 00 10                                      ...."
+        );
+
+        assert!(!ResponseBody::from("{\"id\": 1}\n").is_binary());
+        assert!(!ResponseBody::from("").is_binary());
+    }
+}
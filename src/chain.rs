@@ -0,0 +1,128 @@
+//! Request chaining: resolves references to earlier named responses such as
+//! `{{login.response.body.$.token}}`, `{{login.response.body.//token}}` or
+//! `{{login.response.headers.Location}}`.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+use crate::client::HttpResponse;
+use crate::jsonpath;
+
+/// Resolves `reference` against `responses`. Returns `None` if it is not a
+/// response reference or if the request or selected value doesn't exist.
+pub fn resolve_reference(responses: &HashMap<String, HttpResponse>, reference: &str) -> Option<String> {
+    let (name, selector) = reference.split_once(".response.")?;
+    let response = responses.get(name.trim())?;
+
+    if let Some(header) = selector.strip_prefix("headers.") {
+        return response.headers.get(header).map(str::to_string);
+    }
+
+    match selector.strip_prefix("body")? {
+        "" | ".*" => Some(response.body.text().to_string()),
+        path => select_body(response.body.text(), path.strip_prefix('.')?).ok().flatten(),
+    }
+}
+
+/// Applies a JSONPath (`$...`) or XPath (`/...`) selector to a response body.
+fn select_body(body: &str, selector: &str) -> Result<Option<String>> {
+    if selector.starts_with('$') {
+        let json: serde_json::Value = serde_json::from_str(body).context("Response body is not JSON")?;
+        return Ok(jsonpath::select_one(&json, selector)?.map(|value| match value {
+            serde_json::Value::String(s) => s,
+            other => other.to_string(),
+        }));
+    }
+
+    if selector.starts_with('/') {
+        let package = sxd_document::parser::parse(body)
+            .map_err(|e| anyhow::anyhow!("Response body is not XML: {:?}", e))?;
+        let document = package.as_document();
+        let value = sxd_xpath::evaluate_xpath(&document, selector)
+            .map_err(|e| anyhow::anyhow!("Invalid XPath {}: {:?}", selector, e))?;
+        if let sxd_xpath::Value::Nodeset(nodes) = &value {
+            if nodes.size() == 0 {
+                return Ok(None);
+            }
+        }
+        return Ok(Some(value.string()));
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::ResponseBody;
+    use crate::headers::Headers;
+    use crate::timing::Timings;
+    use std::time::Duration;
+
+    fn response(body: &str, content_type: &str) -> HttpResponse {
+        HttpResponse {
+            request: Default::default(),
+            status: 200,
+            version: reqwest::Version::HTTP_11,
+            redirects: Vec::new(),
+            saved_to: None,
+            timings: Timings::default(),
+            headers: Headers::from([
+                ("Content-Type".to_string(), content_type.to_string()),
+                ("Location".to_string(), "/users/42".to_string()),
+            ]),
+            body: ResponseBody::from(body),
+            content_type: Some(content_type.to_string()),
+            duration: Duration::ZERO,
+            tests: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    fn responses() -> HashMap<String, HttpResponse> {
+        HashMap::from([
+            (
+                "login".to_string(),
+                response(r#"{"token": "abc", "user": {"id": 42}}"#, "application/json"),
+            ),
+            (
+                "Get Feed".to_string(),
+                response("<feed><entry><id>7</id></entry></feed>", "application/xml"),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_resolve_json_body() {
+        assert_eq!(resolve_reference(&responses(), "login.response.body.$.token"), Some("abc".to_string()));
+        assert_eq!(resolve_reference(&responses(), "login.response.body.$.user.id"), Some("42".to_string()));
+        assert_eq!(resolve_reference(&responses(), "login.response.body.$.missing"), None);
+    }
+
+    #[test]
+    fn test_resolve_whole_body() {
+        assert_eq!(
+            resolve_reference(&responses(), "login.response.body.*"),
+            Some(r#"{"token": "abc", "user": {"id": 42}}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_xml_body() {
+        assert_eq!(resolve_reference(&responses(), "Get Feed.response.body.//entry/id"), Some("7".to_string()));
+        assert_eq!(resolve_reference(&responses(), "Get Feed.response.body.//missing"), None);
+    }
+
+    #[test]
+    fn test_resolve_header_case_insensitive() {
+        assert_eq!(resolve_reference(&responses(), "login.response.headers.location"), Some("/users/42".to_string()));
+        assert_eq!(resolve_reference(&responses(), "login.response.headers.X-Missing"), None);
+    }
+
+    #[test]
+    fn test_unknown_references() {
+        assert_eq!(resolve_reference(&responses(), "logout.response.body.$.token"), None);
+        assert_eq!(resolve_reference(&responses(), "login.token"), None);
+        assert_eq!(resolve_reference(&responses(), "login.response.status"), None);
+    }
+}
@@ -1,25 +1,121 @@
 use anyhow::{Context, Result};
 use reqwest::{Client, Method};
 use std::collections::HashMap;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio_util::io::ReaderStream;
 use url::Url;
-use crate::config::HttpClientConfig;
+use crate::body::{self, ResponseBody};
+use crate::config::{HttpClientConfig, HttpVersion};
 use crate::env::EnvironmentManager;
-use crate::parser::HttpRequest;
+use crate::headers::Headers;
+use crate::multipart;
+use crate::parser::{HttpRequest, RequestBody, RequestOptions};
+use crate::script::{self, TestResult};
+use crate::timing::{self, ConnectionTimer, Timings};
 
-#[derive(Debug)]
+/// Time allowed for a response unless the request sets `# @timeout`
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
 pub struct HttpResponse {
+    /// The request that got this response, before any redirects
+    pub request: SentRequest,
     pub status: u16,
-    pub headers: HashMap<String, String>,
-    pub body: String,
+    /// HTTP version the response was received with
+    pub version: reqwest::Version,
+    pub headers: Headers,
+    pub body: ResponseBody,
     pub content_type: Option<String>,
+    /// Time from sending the request until the body was read
+    pub duration: Duration,
+    /// Redirects followed before this response, in order
+    pub redirects: Vec<RedirectHop>,
+    /// File the body was saved to with `>>` or `>>!`
+    pub saved_to: Option<PathBuf>,
+    pub timings: Timings,
+    pub tests: Vec<TestResult>,
+    pub logs: Vec<String>,
+}
+
+/// A request as it was sent, with variables resolved.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SentRequest {
+    pub method: String,
+    pub url: String,
+    /// Headers set by the client itself, such as `Cookie`, are not included
+    pub headers: Headers,
+    /// `None` without a body or when it is streamed, as for files and multipart forms
+    pub body: Option<String>,
+}
+
+impl From<&reqwest::Request> for SentRequest {
+    fn from(request: &reqwest::Request) -> Self {
+        Self {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: Headers::from(request.headers()),
+            body: request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(|bytes| String::from_utf8_lossy(bytes).into_owned()),
+        }
+    }
+}
+
+/// A redirect response that was followed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedirectHop {
+    pub status: u16,
+    pub url: String,
+    /// `Location` resolved against `url`
+    pub location: String,
+    /// All headers of the redirect response, such as `Set-Cookie`
+    pub headers: Headers,
+}
+
+impl std::fmt::Display for RedirectHop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} -> {}", self.status, self.url, self.location)
+    }
+}
+
+impl HttpResponse {
+    pub fn failed_tests(&self) -> usize {
+        self.tests.iter().filter(|t| !t.passed).count()
+    }
+
+    /// The body text that response handlers and assertions check. Fails for
+    /// a body that was saved to a file and too large to keep in memory.
+    pub fn checked_text(&self) -> Result<&str> {
+        match &self.saved_to {
+            Some(path) if self.body.is_omitted() => anyhow::bail!(
+                "Response body too large to check (over {} MiB), saved to {}",
+                MAX_RETAINED_BODY / (1024 * 1024),
+                path.display()
+            ),
+            _ => Ok(self.body.text()),
+        }
+    }
+}
+
+/// Client settings a request overrides; the default overrides nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct ClientVariant {
+    version: Option<HttpVersion>,
+    no_cookie_jar: bool,
+    connect_timeout: Option<Duration>,
 }
 
 pub struct HttpClient {
     client: Client,
     config: HttpClientConfig,
+    /// Clients for requests whose settings differ from `config`
+    variant_clients: Mutex<HashMap<ClientVariant, Client>>,
     env_manager: EnvironmentManager,
     base_path: std::path::PathBuf,
+    connection_timer: ConnectionTimer,
 }
 
 impl HttpClient {
@@ -29,13 +125,18 @@ impl HttpClient {
         base_path: impl AsRef<std::path::Path>,
     ) -> Result<Self> {
         let base_path = base_path.as_ref().to_path_buf();
-        let client = config.build_client(&base_path)?;
+        let connection_timer = ConnectionTimer::new();
+        let config = config.with_connection_timer(connection_timer.clone());
+        // Redirects are followed by `execute_request`
+        let client = config.clone().without_redirects().build_client(&base_path)?;
 
         Ok(Self {
             client,
             config,
+            variant_clients: Mutex::new(HashMap::new()),
             env_manager,
             base_path,
+            connection_timer,
         })
     }
 
@@ -46,8 +147,18 @@ impl HttpClient {
     ) -> Result<HttpResponse> {
         let env_name = env_name.unwrap_or("default");
 
+        // Run pre-request scripts before any variables are resolved
+        let mut variables = HashMap::new();
+        let mut logs = Vec::new();
+        for pre_request in &request.pre_request_scripts {
+            let source = script::load_script(pre_request, &self.base_path)?;
+            let output = script::run_pre_request_script(&source, request, env_name, &self.env_manager)?;
+            variables.extend(output.variables);
+            logs.extend(output.logs);
+        }
+
         // Resolve URI with environment variables
-        let uri = self.env_manager.resolve_string(env_name, &request.uri);
+        let uri = self.env_manager.resolve_string_with(env_name, &request.uri, &variables);
 
         // Parse URL
         let url = Url::parse(&uri)
@@ -57,23 +168,70 @@ impl HttpClient {
         let method = Method::from_bytes(request.method.as_bytes())
             .with_context(|| format!("Invalid HTTP method: {}", request.method))?;
 
-        // Build request
-        let mut req_builder = self.client.request(method, url);
+        // Build request with a client for the request-line HTTP version
+        let version = request.http_version.as_deref().map(HttpVersion::parse).transpose()?;
+        let client = self.client_for(version, &request.options)?;
+        let mut req_builder = client.request(method, url);
+        if let Some(version) = version.or(self.config.http_version) {
+            // Sending fails instead of silently falling back to another version
+            req_builder = req_builder.version(version.version());
+        }
+
+        // Multipart bodies written in the file are sent as a form, which
+        // brings its own Content-Type boundary and Content-Length
+        let multipart_boundary = request
+            .headers
+            .get("Content-Type")
+            .and_then(multipart::boundary)
+            .filter(|_| !matches!(request.body, Some(RequestBody::File(_)) | None));
 
         // Add headers
         for (key, value) in &request.headers {
-            let resolved_value = self.env_manager.resolve_string(env_name, value);
+            if multipart_boundary.is_some()
+                && (key.eq_ignore_ascii_case("Content-Type") || key.eq_ignore_ascii_case("Content-Length"))
+            {
+                continue;
+            }
+            let resolved_value = self.env_manager.resolve_string_with(env_name, value, &variables);
             req_builder = req_builder.header(key, resolved_value);
         }
 
+        if self.config.decompress && !request.headers.contains_key("Accept-Encoding") {
+            req_builder = req_builder.header(reqwest::header::ACCEPT_ENCODING, body::ACCEPT_ENCODING);
+        }
+
         // Add body
-        if let Some(body) = &request.body {
-            let resolved_body = self.env_manager.resolve_string(env_name, body);
-            
+        let body = match &request.body {
+            Some(RequestBody::Inline(text)) => Some(text.clone()),
+            Some(RequestBody::TemplateFile(path)) => {
+                let path = self.body_path(env_name, path, &variables);
+                let template = tokio::fs::read_to_string(&path)
+                    .await
+                    .with_context(|| format!("Failed to read body file: {}", path.display()))?;
+                Some(template)
+            }
+            Some(RequestBody::File(path)) => {
+                let path = self.body_path(env_name, path, &variables);
+                let file = tokio::fs::File::open(&path)
+                    .await
+                    .with_context(|| format!("Failed to open body file: {}", path.display()))?;
+                let length = file.metadata().await?.len();
+                if !request.headers.contains_key("Content-Length") {
+                    req_builder = req_builder.header(reqwest::header::CONTENT_LENGTH, length);
+                }
+                // Stream the file as is, without loading it into memory
+                req_builder = req_builder.body(reqwest::Body::wrap_stream(ReaderStream::new(file)));
+                None
+            }
+            None => None,
+        };
+
+        if let Some(body) = body {
+            let resolved_body = self.env_manager.resolve_string_with(env_name, &body, &variables);
+
             // Check content type
             let content_type = request.headers
                 .get("Content-Type")
-                .or_else(|| request.headers.get("content-type"))
                 .map(|s| s.to_lowercase());
 
             match content_type.as_deref() {
@@ -94,9 +252,15 @@ impl HttpClient {
                     req_builder = req_builder.form(&form_data);
                 }
                 Some(ct) if ct.starts_with("multipart/form-data") => {
-                    // Handle multipart form data
-                    // This is simplified - full implementation would parse the body properly
-                    req_builder = req_builder.body(resolved_body);
+                    let boundary = multipart_boundary
+                        .as_deref()
+                        .context("multipart/form-data Content-Type is missing a boundary")?;
+                    let parts = multipart::parse(&resolved_body, boundary)?;
+                    let form = multipart::build_form(parts, &self.base_path, |text| {
+                        self.env_manager.resolve_string_with(env_name, text, &variables)
+                    })
+                    .await?;
+                    req_builder = req_builder.multipart(form);
                 }
                 _ => {
                     req_builder = req_builder.body(resolved_body);
@@ -104,21 +268,65 @@ impl HttpClient {
             }
         }
 
-        // Execute request
-        let response = req_builder
-            .timeout(Duration::from_secs(30))
-            .send()
-            .await
-            .context("Failed to send HTTP request")?;
+        // Execute request, following redirects here so every hop is recorded
+        let follow_redirects = self.config.follow_redirects && !request.options.no_redirect;
+        let max_redirects = request.options.max_redirects.unwrap_or(self.config.max_redirects);
+        let mut next = req_builder
+            .timeout(request.options.timeout.unwrap_or(DEFAULT_TIMEOUT))
+            .build()
+            .context("Failed to build HTTP request")?;
+        let mut redirects = Vec::new();
+        self.connection_timer.take();
+        let request_size = timing::request_size(&next);
+        let sent = SentRequest::from(&next);
+        let started = Instant::now();
+        let response = loop {
+            let template = without_body(&next);
+            let retry = next.try_clone();
+            let tls = next.url().scheme() == "https";
+            let response = self
+                .connection_timer
+                .send(tls, client.execute(next))
+                .await
+                .context("Failed to send HTTP request")?;
+            if !follow_redirects || !response.status().is_redirection() {
+                break response;
+            }
+            let Some(location) = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+            else {
+                break response;
+            };
+            let target = response
+                .url()
+                .join(location)
+                .with_context(|| format!("Invalid redirect Location: {}", location))?;
 
+            let hop = RedirectHop {
+                status: response.status().as_u16(),
+                url: response.url().to_string(),
+                location: target.to_string(),
+                headers: Headers::from(response.headers()),
+            };
+            redirects.push(hop);
+            if redirects.len() > max_redirects {
+                anyhow::bail!(
+                    "Too many redirects (more than {}):\n{}",
+                    max_redirects,
+                    redirects.iter().map(|hop| format!("  {}", hop)).collect::<Vec<_>>().join("\n")
+                );
+            }
+            next = redirect_request(template, retry, response.status(), response.url(), target)?;
+        };
+
+        let ttfb = started.elapsed();
+        let connection = self.connection_timer.take();
+        let response_head_size = timing::response_head_size(&response);
         let status = response.status().as_u16();
-        let headers: HashMap<String, String> = response
-            .headers()
-            .iter()
-            .map(|(k, v)| {
-                (k.to_string(), v.to_str().unwrap_or("").to_string())
-            })
-            .collect();
+        let response_version = response.version();
+        let headers = Headers::from(response.headers());
 
         let content_type = response
             .headers()
@@ -126,51 +334,351 @@ impl HttpClient {
             .and_then(|v| v.to_str().ok())
             .map(|s| s.to_string());
 
-        let body = response
-            .text()
-            .await
-            .context("Failed to read response body")?;
+        let content_encoding = response
+            .headers()
+            .get(reqwest::header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string());
 
-        Ok(HttpResponse {
+        let (body, saved_to) = match &request.output {
+            Some(output) => {
+                let path = output_path(&self.body_path(env_name, &output.path, &variables), output.overwrite);
+                let body = save_response(response, &path, content_type.as_deref(), content_encoding.as_deref(), self.config.decompress)
+                    .await
+                    .with_context(|| format!("Failed to save response to {}", path.display()))?;
+                (body, Some(path))
+            }
+            None => {
+                let received = response
+                    .bytes()
+                    .await
+                    .context("Failed to read response body")?;
+                let body = ResponseBody::new(
+                    received,
+                    content_type.as_deref(),
+                    content_encoding.as_deref(),
+                    self.config.decompress,
+                )
+                .with_context(|| format!("Failed to decode {} response body", content_encoding.as_deref().unwrap_or_default()))?;
+                (body, None)
+            }
+        };
+        let duration = started.elapsed();
+        let timings = Timings {
+            dns: connection.dns,
+            connect: connection.connect,
+            tls: connection.tls,
+            ttfb,
+            download: duration - ttfb,
+            total: duration,
+            request_size,
+            response_size: response_head_size + body.encoded_len() as u64,
+        };
+
+        if let Some(jar) = self.config.cookie_jar.as_ref().filter(|_| !request.options.no_cookie_jar) {
+            jar.save()?;
+        }
+
+        let mut response = HttpResponse {
+            request: sent,
             status,
+            version: response_version,
             headers,
             body,
             content_type,
-        })
+            duration,
+            redirects,
+            saved_to,
+            timings,
+            tests: Vec::new(),
+            logs,
+        };
+
+        // Run response handler scripts, unless the body they see is missing
+        if let Some(error) = response.checked_text().err().filter(|_| !request.response_handlers.is_empty()) {
+            let message = Some(error.to_string());
+            response.tests.push(TestResult { name: "Response handler".to_string(), passed: false, message });
+        } else {
+            for handler in &request.response_handlers {
+                let source = script::load_script(handler, &self.base_path)?;
+                let output = script::run_response_handler(&source, &response, &self.env_manager)?;
+                response.tests.extend(output.tests);
+                response.logs.extend(output.logs);
+            }
+        }
+
+        // Check declarative assertions
+        for assertion in &request.assertions {
+            let result = assertion.check(&response);
+            response.tests.push(result);
+        }
+
+        Ok(response)
+    }
+
+    /// Returns the client for a request, building and caching one when the
+    /// request needs settings that differ from the configured client.
+    fn client_for(&self, version: Option<HttpVersion>, options: &RequestOptions) -> Result<Client> {
+        let variant = ClientVariant {
+            version: version.filter(|v| Some(*v) != self.config.http_version),
+            no_cookie_jar: options.no_cookie_jar && self.config.cookie_jar.is_some(),
+            connect_timeout: options.connection_timeout.filter(|t| Some(*t) != self.config.connect_timeout),
+        };
+        if variant == ClientVariant::default() {
+            return Ok(self.client.clone());
+        }
+
+        let mut clients = self.variant_clients.lock().unwrap();
+        if let Some(client) = clients.get(&variant) {
+            return Ok(client.clone());
+        }
+        let mut config = self.config.clone().without_redirects();
+        if let Some(version) = variant.version {
+            config = config.with_http_version(version);
+        }
+        if variant.no_cookie_jar {
+            config.cookie_jar = None;
+        }
+        if let Some(timeout) = variant.connect_timeout {
+            config = config.with_connect_timeout(timeout);
+        }
+        let client = config.build_client(&self.base_path)?;
+        clients.insert(variant, client.clone());
+        Ok(client)
+    }
+
+    /// Resolves a body or output file path relative to the `.http` file.
+    fn body_path(&self, env_name: &str, path: &str, variables: &HashMap<String, String>) -> PathBuf {
+        let path = self.env_manager.resolve_string_with(env_name, path, variables);
+        self.base_path.join(path)
     }
 
     pub fn print_response(&self, response: &HttpResponse) {
-        println!("HTTP/1.1 {}", response.status);
+        if !response.redirects.is_empty() {
+            println!("Redirects:");
+            for hop in &response.redirects {
+                println!("  {}", hop);
+                for value in hop.headers.get_all("set-cookie") {
+                    println!("    set-cookie: {}", value);
+                }
+            }
+            println!();
+        }
+        println!("{:?} {}", response.version, response.status);
+        println!("Timing: {}", response.timings);
         for (key, value) in &response.headers {
             println!("{}: {}", key, value);
         }
         println!();
-        println!("{}", response.body);
+        if response.body.is_decoded() {
+            println!(
+                "(decoded {}: {} -> {} bytes)",
+                response.body.content_encoding().unwrap_or_default(),
+                response.body.encoded_len(),
+                response.body.len()
+            );
+        }
+        if let Some(path) = &response.saved_to {
+            println!("Response body saved to {}", path.display());
+        } else if response.body.is_binary() {
+            println!("{}", response.body.summary());
+        } else {
+            println!("{}", response.body.text());
+        }
+
+        for log in &response.logs {
+            println!("{}", log);
+        }
+
+        if !response.tests.is_empty() {
+            println!();
+            println!("Tests:");
+            for test in &response.tests {
+                match (&test.passed, &test.message) {
+                    (true, _) => println!("  [PASS] {}", test.name),
+                    (false, Some(message)) => println!("  [FAIL] {}: {}", test.name, message),
+                    (false, None) => println!("  [FAIL] {}", test.name),
+                }
+            }
+        }
+    }
+}
+
+/// Bodies saved to a file are also kept in memory up to this size, for
+/// response handlers and assertions
+const MAX_RETAINED_BODY: usize = 1024 * 1024;
+
+/// Returns `path`, or unless `overwrite` is set and the file exists, the first
+/// free `name-1.ext`, `name-2.ext`, ...
+fn output_path(path: &Path, overwrite: bool) -> PathBuf {
+    if overwrite || !path.exists() {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Streams the response body to `path`, decompressing it on the way.
+async fn save_response(
+    response: reqwest::Response,
+    path: &Path,
+    content_type: Option<&str>,
+    content_encoding: Option<&str>,
+    decompress: bool,
+) -> Result<ResponseBody> {
+    use futures_util::StreamExt;
+    use tokio::io::AsyncWriteExt;
+
+    let decode = decompress && content_encoding.is_some_and(body::is_supported);
+    let mut decoder = body::StreamDecoder::new(content_encoding.filter(|_| decode))?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut file = tokio::fs::File::create(path).await?;
+
+    let mut received = 0;
+    let mut retained = Some(Vec::new());
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.context("Failed to read response body")?;
+        received += chunk.len();
+        let decoded = decoder.write(&chunk)?;
+        file.write_all(&decoded).await?;
+        retain(&mut retained, &decoded);
     }
+    let decoded = decoder.finish()?;
+    file.write_all(&decoded).await?;
+    file.flush().await?;
+    retain(&mut retained, &decoded);
+
+    Ok(ResponseBody::streamed(
+        retained.map(Into::into),
+        content_type,
+        content_encoding,
+        decode,
+        received,
+    ))
+}
+
+/// Keeps `data` in memory until the body grows past `MAX_RETAINED_BODY`.
+fn retain(retained: &mut Option<Vec<u8>>, data: &[u8]) {
+    if let Some(bytes) = retained {
+        if bytes.len() + data.len() > MAX_RETAINED_BODY {
+            *retained = None;
+        } else {
+            bytes.extend_from_slice(data);
+        }
+    }
+}
+
+/// A copy of `request` without its body, which may be a stream that cannot
+/// be cloned.
+fn without_body(request: &reqwest::Request) -> reqwest::Request {
+    let mut copy = reqwest::Request::new(request.method().clone(), request.url().clone());
+    *copy.headers_mut() = request.headers().clone();
+    *copy.timeout_mut() = request.timeout().copied();
+    *copy.version_mut() = request.version();
+    copy
+}
+
+/// Builds the request that follows a redirect to `target`. 303 responses, and
+/// 301/302 responses to a POST, switch to a GET without a body, as browsers
+/// do, starting from `template`, the previous request without its body.
+/// Otherwise `retry` is resent as is, which fails for a streamed body.
+/// Credentials are not sent to another scheme, host or port.
+fn redirect_request(
+    template: reqwest::Request,
+    retry: Option<reqwest::Request>,
+    status: reqwest::StatusCode,
+    from: &Url,
+    target: Url,
+) -> Result<reqwest::Request> {
+    let method = template.method().clone();
+    let to_get = status == reqwest::StatusCode::SEE_OTHER && method != Method::HEAD
+        || matches!(status.as_u16(), 301 | 302) && method == Method::POST;
+
+    let mut request = if to_get {
+        let mut request = template;
+        *request.method_mut() = Method::GET;
+        *request.url_mut() = target.clone();
+        for header in [reqwest::header::CONTENT_TYPE, reqwest::header::CONTENT_LENGTH, reqwest::header::TRANSFER_ENCODING] {
+            request.headers_mut().remove(header);
+        }
+        request
+    } else {
+        let mut request = retry
+            .with_context(|| format!("Cannot resend a streamed request body to follow a {} redirect", status.as_u16()))?;
+        *request.url_mut() = target.clone();
+        request
+    };
+
+    if from.scheme() != target.scheme()
+        || from.host_str() != target.host_str()
+        || from.port_or_known_default() != target.port_or_known_default()
+    {
+        for header in [reqwest::header::AUTHORIZATION, reqwest::header::COOKIE, reqwest::header::PROXY_AUTHORIZATION] {
+            request.headers_mut().remove(header);
+        }
+    }
+    Ok(request)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::HttpClientConfig;
+    use crate::cookies::CookieJar;
+    use crate::assertion::Assertion;
+    use crate::parser::{OutputFile, ScriptSource, Span};
     use std::collections::HashMap;
 
     #[test]
     fn test_http_response_creation() {
         let response = HttpResponse {
+            request: Default::default(),
             status: 200,
-            headers: {
-                let mut h = HashMap::new();
-                h.insert("Content-Type".to_string(), "application/json".to_string());
-                h
-            },
-            body: r#"{"message": "success"}"#.to_string(),
+            version: reqwest::Version::HTTP_11,
+            redirects: Vec::new(),
+            saved_to: None,
+            timings: Timings::default(),
+            headers: Headers::from([("Content-Type", "application/json")]),
+            body: ResponseBody::from(r#"{"message": "success"}"#),
             content_type: Some("application/json".to_string()),
+            duration: Duration::from_millis(5),
+            tests: Vec::new(),
+            logs: Vec::new(),
         };
 
         assert_eq!(response.status, 200);
-        assert_eq!(response.headers.get("Content-Type"), Some(&"application/json".to_string()));
-        assert!(response.body.contains("success"));
+        assert_eq!(response.headers.get("Content-Type"), Some("application/json"));
+        assert!(response.body.text().contains("success"));
+    }
+
+    #[test]
+    fn test_failed_tests_count() {
+        let response = HttpResponse {
+            request: Default::default(),
+            status: 500,
+            version: reqwest::Version::HTTP_11,
+            redirects: Vec::new(),
+            saved_to: None,
+            timings: Timings::default(),
+            headers: Headers::new(),
+            body: ResponseBody::default(),
+            content_type: None,
+            duration: Duration::ZERO,
+            tests: vec![
+                TestResult { name: "ok".to_string(), passed: true, message: None },
+                TestResult { name: "bad".to_string(), passed: false, message: Some("status".to_string()) },
+            ],
+            logs: Vec::new(),
+        };
+
+        assert_eq!(response.failed_tests(), 1);
     }
 
     #[tokio::test]
@@ -181,6 +689,512 @@ mod tests {
         assert!(client.is_ok());
     }
 
+    /// Accepts a single connection, answers `200 OK` and returns the raw request.
+    async fn capture_request() -> (String, tokio::task::JoinHandle<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut raw = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                raw.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&raw).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if raw.len() >= end + 4 + length || n == 0 {
+                        break;
+                    }
+                }
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&raw).to_string()
+        });
+        (url, handle)
+    }
+
+    fn post(uri: &str, body: RequestBody) -> HttpRequest {
+        HttpRequest {
+            name: None,
+            method: "POST".to_string(),
+            uri: uri.to_string(),
+            http_version: None,
+            headers: Headers::from([("Content-Type".to_string(), "text/plain".to_string())]),
+            body: Some(body),
+            comments: Vec::new(),
+            header_comments: Vec::new(),
+            pre_request_scripts: Vec::new(),
+            response_handlers: Vec::new(),
+            assertions: Vec::new(),
+            options: RequestOptions::default(),
+            output: None,
+            tags: Vec::new(),
+            span: Span::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_bodies_relative_to_http_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("payload.txt"), "id={{id}}").unwrap();
+        let env_manager = EnvironmentManager::new(dir.path());
+        env_manager.set_file_variables(&[("id".to_string(), "42".to_string())]);
+        let client = HttpClient::new(HttpClientConfig::new(), env_manager, dir.path()).unwrap();
+
+        let (url, server) = capture_request().await;
+        let response = client.execute_request(&post(&url, RequestBody::File("./payload.txt".to_string())), None).await.unwrap();
+        let raw = server.await.unwrap();
+        assert!(raw.to_lowercase().contains("content-length: 9"));
+        assert!(raw.ends_with("\r\n\r\nid={{id}}"));
+        // Streamed, so not captured
+        assert_eq!(response.request.body, None);
+
+        let (url, server) = capture_request().await;
+        let response = client.execute_request(&post(&url, RequestBody::TemplateFile("payload.txt".to_string())), None).await.unwrap();
+        assert!(server.await.unwrap().ends_with("\r\n\r\nid=42"));
+        assert_eq!(response.request.method, "POST");
+        assert_eq!(response.request.url, format!("{}/", url));
+        assert_eq!(response.request.headers.get("Content-Type"), Some("text/plain"));
+        assert_eq!(response.request.body.as_deref(), Some("id=42"));
+    }
+
+    #[tokio::test]
+    async fn test_multipart_body_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("request-form-data.json"), "{\"id\": 1}").unwrap();
+        let client = HttpClient::new(HttpClientConfig::new(), EnvironmentManager::new(dir.path()), dir.path()).unwrap();
+
+        let (url, server) = capture_request().await;
+        let mut request = post(&url, RequestBody::Inline(
+            "--WebAppBoundary\n\
+             Content-Disposition: form-data; name=\"element-name\"\n\
+             Content-Type: text/plain\n\n\
+             Name\n\
+             --WebAppBoundary\n\
+             Content-Disposition: form-data; name=\"data\"; filename=\"data.json\"\n\
+             Content-Type: application/json\n\n\
+             < ./request-form-data.json\n\
+             --WebAppBoundary--"
+                .to_string(),
+        ));
+        request.headers.insert(
+            "Content-Type".to_string(),
+            "multipart/form-data; boundary=WebAppBoundary".to_string(),
+        );
+        client.execute_request(&request, None).await.unwrap();
+
+        let raw = server.await.unwrap();
+        let (head, body) = raw.split_once("\r\n\r\n").unwrap();
+        let content_types: Vec<&str> = head
+            .lines()
+            .filter_map(|l| l.strip_prefix("content-type: "))
+            .collect();
+        assert_eq!(content_types.len(), 1);
+        let boundary = multipart::boundary(content_types[0]).unwrap();
+
+        let parts = multipart::parse(body, &boundary).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].disposition(), (Some("element-name".to_string()), None));
+        assert_eq!(parts[0].header("Content-Type"), Some("text/plain"));
+        assert_eq!(parts[0].content, RequestBody::Inline("Name".to_string()));
+        assert_eq!(parts[1].disposition(), (Some("data".to_string()), Some("data.json".to_string())));
+        assert_eq!(parts[1].header("Content-Type"), Some("application/json"));
+        assert_eq!(parts[1].content, RequestBody::Inline("{\"id\": 1}".to_string()));
+    }
+
+    /// Serves HTTP/1.1 and prior-knowledge HTTP/2, echoing the request version.
+    fn serve_versions() -> String {
+        use hyper::service::{make_service_fn, service_fn};
+
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(service_fn(|req: hyper::Request<hyper::Body>| async move {
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(hyper::Body::from(format!("{:?}", req.version()))))
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    #[tokio::test]
+    async fn test_request_line_http_version() {
+        let client = HttpClient::new(HttpClientConfig::new(), EnvironmentManager::new("."), ".").unwrap();
+        let url = serve_versions();
+        let mut request = post(&url, RequestBody::Inline("ping".to_string()));
+
+        request.http_version = Some("HTTP/2 (Prior Knowledge)".to_string());
+        let response = client.execute_request(&request, None).await.unwrap();
+        assert_eq!(response.version, reqwest::Version::HTTP_2);
+        assert_eq!(response.body.text(), "HTTP/2.0");
+
+        request.http_version = Some("HTTP/1.1".to_string());
+        let response = client.execute_request(&request, None).await.unwrap();
+        assert_eq!(response.version, reqwest::Version::HTTP_11);
+        assert_eq!(response.body.text(), "HTTP/1.1");
+
+        request.http_version = Some("HTTP/1.0".to_string());
+        let response = client.execute_request(&request, None).await.unwrap();
+        assert_eq!(response.body.text(), "HTTP/1.0");
+
+        // HTTP/2 is negotiated with ALPN, which plain http cannot do
+        request.http_version = Some("HTTP/2".to_string());
+        assert!(client.execute_request(&request, None).await.is_err());
+
+        request.http_version = Some("HTTP/3".to_string());
+        assert!(client.execute_request(&request, None).await.is_err());
+    }
+
+    /// Sets `session` and `theme` cookies on `/login` and echoes the `Cookie` header elsewhere.
+    fn serve_cookies() -> String {
+        use hyper::service::{make_service_fn, service_fn};
+
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(service_fn(|req: hyper::Request<hyper::Body>| async move {
+                let response = if req.uri().path() == "/login" {
+                    hyper::Response::builder()
+                        .header("Set-Cookie", "session=abc; Path=/")
+                        .header("Set-Cookie", "theme=dark; Path=/")
+                        .body(hyper::Body::empty())
+                        .unwrap()
+                } else {
+                    let cookie = req.headers().get("cookie").map(|v| v.to_str().unwrap().to_string());
+                    hyper::Response::new(hyper::Body::from(cookie.unwrap_or_default()))
+                };
+                Ok::<_, std::convert::Infallible>(response)
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    #[tokio::test]
+    async fn test_cookie_jar_persists_cookies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("http-client.cookies");
+        let url = serve_cookies();
+        let request = |path: &str| post(&format!("{}{}", url, path), RequestBody::Inline(String::new()));
+        let client = |jar: CookieJar| {
+            let config = HttpClientConfig::new().with_cookie_jar(std::sync::Arc::new(jar));
+            HttpClient::new(config, EnvironmentManager::new(dir.path()), dir.path()).unwrap()
+        };
+
+        let first_run = client(CookieJar::load(&path).unwrap());
+        let mut anonymous = request("/login");
+        anonymous.options.no_cookie_jar = true;
+        first_run.execute_request(&anonymous, None).await.unwrap();
+        assert!(!path.exists());
+
+        first_run.execute_request(&request("/login"), None).await.unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("session\tabc\t-1"));
+        assert_eq!(first_run.execute_request(&request("/me"), None).await.unwrap().body.text(), "session=abc; theme=dark");
+
+        // A new run loads the saved cookies, unless the request opts out
+        let second_run = client(CookieJar::load(&path).unwrap());
+        assert_eq!(second_run.execute_request(&request("/me"), None).await.unwrap().body.text(), "session=abc; theme=dark");
+        let mut anonymous = request("/me");
+        anonymous.options.no_cookie_jar = true;
+        assert_eq!(second_run.execute_request(&anonymous, None).await.unwrap().body.text(), "");
+    }
+
+    #[tokio::test]
+    async fn test_repeated_headers() {
+        let client = HttpClient::new(HttpClientConfig::new(), EnvironmentManager::new("."), ".").unwrap();
+
+        let (url, server) = capture_request().await;
+        let mut request = post(&url, RequestBody::Inline(String::new()));
+        request.headers.append("Accept", "text/html");
+        request.headers.append("X-Trace", "1");
+        request.headers.append("accept", "application/json");
+        client.execute_request(&request, None).await.unwrap();
+        let raw = server.await.unwrap().to_lowercase();
+        let html = raw.find("accept: text/html").unwrap();
+        assert!(raw[html..].contains("accept: application/json"));
+
+        let mut request = post(&format!("{}/login", serve_cookies()), RequestBody::Inline(String::new()));
+        request.options.no_cookie_jar = true;
+        let response = client.execute_request(&request, None).await.unwrap();
+        assert_eq!(
+            response.headers.get_all("Set-Cookie").collect::<Vec<_>>(),
+            ["session=abc; Path=/", "theme=dark; Path=/"]
+        );
+    }
+
+    /// Redirects `/redirect` to `/target`, which echoes the method and body,
+    /// answers `/slow` after a second and `/large` with 2 MiB.
+    fn serve_redirects() -> String {
+        use hyper::service::{make_service_fn, service_fn};
+
+        let redirect = |status: u16, location: &str| {
+            hyper::Response::builder()
+                .status(status)
+                .header("Location", location)
+                .header("Set-Cookie", format!("hop={}", status))
+                .body(hyper::Body::empty())
+                .unwrap()
+        };
+        let make_service = make_service_fn(move |_| async move {
+            Ok::<_, std::convert::Infallible>(service_fn(move |req: hyper::Request<hyper::Body>| async move {
+                let response = match req.uri().path() {
+                    "/redirect" => redirect(302, "/target"),
+                    "/sso" => redirect(301, "/redirect"),
+                    "/temporary" => redirect(307, "/target"),
+                    "/see-other" => redirect(303, "/target"),
+                    "/loop" => redirect(302, "/loop"),
+                    "/large" => hyper::Response::new(hyper::Body::from(vec![b'a'; 2 * MAX_RETAINED_BODY])),
+                    "/slow" => {
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        hyper::Response::new(hyper::Body::from("slow"))
+                    }
+                    _ => {
+                        let method = req.method().to_string();
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        hyper::Response::new(hyper::Body::from(format!("{} {}", method, String::from_utf8_lossy(&body))))
+                    }
+                };
+                Ok::<_, std::convert::Infallible>(response)
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    #[tokio::test]
+    async fn test_redirect_chain() {
+        let client = HttpClient::new(HttpClientConfig::new(), EnvironmentManager::new("."), ".").unwrap();
+        let url = serve_redirects();
+
+        let response = client
+            .execute_request(&post(&format!("{}/sso", url), RequestBody::Inline("data".to_string())), None)
+            .await
+            .unwrap();
+        assert_eq!((response.status, response.body.text()), (200, "GET "));
+        let hops: Vec<String> = response.redirects.iter().map(|hop| hop.to_string()).collect();
+        assert_eq!(
+            hops,
+            vec![
+                format!("301 {}/sso -> {}/redirect", url, url),
+                format!("302 {}/redirect -> {}/target", url, url),
+            ]
+        );
+        assert_eq!(response.redirects[0].headers.get("Set-Cookie"), Some("hop=301"));
+
+        // 307 keeps the method and body
+        let response = client
+            .execute_request(&post(&format!("{}/temporary", url), RequestBody::Inline("data".to_string())), None)
+            .await
+            .unwrap();
+        assert_eq!(response.body.text(), "POST data");
+
+        let mut request = post(&format!("{}/sso", url), RequestBody::Inline(String::new()));
+        request.options.max_redirects = Some(1);
+        let error = client.execute_request(&request, None).await.unwrap_err();
+        assert!(error.to_string().contains("Too many redirects (more than 1)"));
+        assert!(error.to_string().contains("/redirect -> "));
+
+        let request = post(&format!("{}/loop", url), RequestBody::Inline(String::new()));
+        assert!(client.execute_request(&request, None).await.is_err());
+
+        // A streamed file body only prevents redirects that resend it
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("payload.txt"), "data").unwrap();
+        let client_in_dir = HttpClient::new(HttpClientConfig::new(), EnvironmentManager::new(dir.path()), dir.path()).unwrap();
+        let request = post(&format!("{}/see-other", url), RequestBody::File("payload.txt".to_string()));
+        let response = client_in_dir.execute_request(&request, None).await.unwrap();
+        assert_eq!((response.status, response.body.text()), (200, "GET "));
+        let request = post(&format!("{}/temporary", url), RequestBody::File("payload.txt".to_string()));
+        let error = client_in_dir.execute_request(&request, None).await.unwrap_err();
+        assert!(error.to_string().contains("Cannot resend a streamed request body to follow a 307 redirect"));
+
+        // Without following, the first 3xx is returned as the response
+        let config = HttpClientConfig::new().without_redirects();
+        let client = HttpClient::new(config, EnvironmentManager::new("."), ".").unwrap();
+        let response = client.execute_request(&post(&format!("{}/sso", url), RequestBody::Inline(String::new())), None).await.unwrap();
+        assert_eq!(response.status, 301);
+        assert!(response.redirects.is_empty());
+    }
+
+    #[test]
+    fn test_redirect_drops_credentials_on_another_origin() {
+        let mut request = reqwest::Request::new(Method::GET, Url::parse("https://example.com/a").unwrap());
+        request.headers_mut().insert(reqwest::header::AUTHORIZATION, "Bearer token".parse().unwrap());
+        request.headers_mut().insert(reqwest::header::COOKIE, "session=abc".parse().unwrap());
+        let from = request.url().clone();
+        let redirect = |target: &str| {
+            let retry = request.try_clone();
+            let target = Url::parse(target).unwrap();
+            redirect_request(without_body(&request), retry, reqwest::StatusCode::FOUND, &from, target).unwrap()
+        };
+
+        let same = redirect("https://example.com/b");
+        assert!(same.headers().contains_key(reqwest::header::AUTHORIZATION));
+        assert!(same.headers().contains_key(reqwest::header::COOKIE));
+        for target in ["http://example.com/b", "https://other.com/b", "https://example.com:8443/b"] {
+            let other = redirect(target);
+            assert!(!other.headers().contains_key(reqwest::header::AUTHORIZATION), "{}", target);
+            assert!(!other.headers().contains_key(reqwest::header::COOKIE), "{}", target);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_options() {
+        let client = HttpClient::new(HttpClientConfig::new(), EnvironmentManager::new("."), ".").unwrap();
+        let url = serve_redirects();
+
+        let mut request = post(&format!("{}/redirect", url), RequestBody::Inline(String::new()));
+        let response = client.execute_request(&request, None).await.unwrap();
+        assert_eq!((response.status, response.body.text()), (200, "GET "));
+
+        request.options.no_redirect = true;
+        let response = client.execute_request(&request, None).await.unwrap();
+        assert_eq!(response.status, 302);
+        assert_eq!(response.headers.get("location"), Some("/target"));
+
+        let mut request = post(&format!("{}/slow", url), RequestBody::Inline(String::new()));
+        request.options.timeout = Some(Duration::from_millis(100));
+        assert!(client.execute_request(&request, None).await.is_err());
+        request.options.timeout = Some(Duration::from_secs(5));
+        assert_eq!(client.execute_request(&request, None).await.unwrap().body.text(), "slow");
+    }
+
+    #[tokio::test]
+    async fn test_compressed_and_binary_bodies() {
+        use hyper::service::{make_service_fn, service_fn};
+        use std::io::Write;
+
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(service_fn(|req: hyper::Request<hyper::Body>| async move {
+                let response = if req.uri().path() == "/image" {
+                    hyper::Response::builder()
+                        .header("Content-Type", "image/png")
+                        .body(hyper::Body::from(&b"\x89PNG\r\n\x1a\n\x00\xff"[..]))
+                } else {
+                    let accept = req.headers().get("accept-encoding").unwrap().to_str().unwrap().to_string();
+                    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                    encoder.write_all(accept.as_bytes()).unwrap();
+                    hyper::Response::builder()
+                        .header("Content-Encoding", "gzip")
+                        .header("Content-Type", "text/plain; charset=utf-8")
+                        .body(hyper::Body::from(encoder.finish().unwrap()))
+                };
+                Ok::<_, std::convert::Infallible>(response.unwrap())
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        let client = HttpClient::new(HttpClientConfig::new(), EnvironmentManager::new("."), ".").unwrap();
+        let response = client.execute_request(&post(&url, RequestBody::Inline(String::new())), None).await.unwrap();
+        assert_eq!(response.body.text(), body::ACCEPT_ENCODING);
+        assert!(response.body.is_decoded());
+        assert_eq!(response.body.content_encoding(), Some("gzip"));
+
+        let response = client
+            .execute_request(&post(&format!("{}/image", url), RequestBody::Inline(String::new())), None)
+            .await
+            .unwrap();
+        assert_eq!(response.body.bytes(), b"\x89PNG\r\n\x1a\n\x00\xff");
+        assert!(response.body.is_binary());
+
+        let config = HttpClientConfig::new().without_decompression();
+        let client = HttpClient::new(config, EnvironmentManager::new("."), ".").unwrap();
+        let mut request = post(&url, RequestBody::Inline(String::new()));
+        request.headers.insert("Accept-Encoding".to_string(), "gzip".to_string());
+        let response = client.execute_request(&request, None).await.unwrap();
+        assert!(!response.body.is_decoded());
+        assert_eq!(body::decode_content(response.body.bytes(), "gzip").unwrap(), b"gzip");
+    }
+
+    #[tokio::test]
+    async fn test_save_response_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let env_manager = EnvironmentManager::new(dir.path());
+        env_manager.set_file_variables(&[("name".to_string(), "report".to_string())]);
+        let client = HttpClient::new(HttpClientConfig::new(), env_manager, dir.path()).unwrap();
+        let url = serve_redirects();
+
+        let mut request = post(&format!("{}/target", url), RequestBody::Inline("first".to_string()));
+        request.output = Some(OutputFile { path: "out/{{name}}.txt".to_string(), overwrite: false });
+        let response = client.execute_request(&request, None).await.unwrap();
+        let first = dir.path().join("out/report.txt");
+        assert_eq!(response.saved_to.as_deref(), Some(first.as_path()));
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "POST first");
+        assert_eq!(response.body.text(), "POST first");
+
+        // `>>` keeps the existing file, `>>!` replaces it
+        request.body = Some(RequestBody::Inline("second".to_string()));
+        let response = client.execute_request(&request, None).await.unwrap();
+        assert_eq!(response.saved_to, Some(dir.path().join("out/report-1.txt")));
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "POST first");
+
+        request.output = Some(OutputFile { path: "out/report.txt".to_string(), overwrite: true });
+        client.execute_request(&request, None).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "POST second");
+
+        // Too large to keep in memory, so checks of the body fail instead of seeing ""
+        let mut request = post(&format!("{}/large", url), RequestBody::Inline(String::new()));
+        request.output = Some(OutputFile { path: "large.txt".to_string(), overwrite: true });
+        request.response_handlers = vec![ScriptSource::Inline("client.log(response.body);".to_string())];
+        request.assertions = vec![
+            Assertion::parse("status == 200").unwrap(),
+            Assertion::parse("body contains a").unwrap(),
+        ];
+        let response = client.execute_request(&request, None).await.unwrap();
+        assert_eq!(std::fs::metadata(dir.path().join("large.txt")).unwrap().len(), 2 * MAX_RETAINED_BODY as u64);
+        let failed: Vec<(&str, &str)> = response
+            .tests
+            .iter()
+            .filter(|t| !t.passed)
+            .map(|t| (t.name.as_str(), t.message.as_deref().unwrap()))
+            .collect();
+        let message = format!("Response body too large to check (over 1 MiB), saved to {}", dir.path().join("large.txt").display());
+        assert_eq!(failed, [("Response handler", message.as_str()), ("@assert body contains a", message.as_str())]);
+        assert!(response.tests[1].passed);
+    }
+
+    #[tokio::test]
+    async fn test_timings() {
+        let client = HttpClient::new(HttpClientConfig::new(), EnvironmentManager::new("."), ".").unwrap();
+        let url = serve_redirects();
+
+        // Looked up on the connection the request opens
+        let url = url.replace("127.0.0.1", "localhost");
+        let request = post(&format!("{}/slow", url), RequestBody::Inline("ping".to_string()));
+        let timings = client.execute_request(&request, None).await.unwrap().timings;
+        assert!(timings.dns.is_some() && timings.connect.is_some());
+        assert_eq!(timings.tls, None);
+        assert!(timings.ttfb >= Duration::from_secs(1));
+        assert_eq!(timings.ttfb + timings.download, timings.total);
+        assert!(timings.request_size > "POST /slow HTTP/1.1\r\n\r\nping".len() as u64);
+        assert!(timings.response_size > "slow".len() as u64);
+
+        // The connection to the origin is reused
+        let timings = client.execute_request(&request, None).await.unwrap().timings;
+        assert_eq!((timings.dns, timings.connect), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_missing_body_file() {
+        let client = HttpClient::new(HttpClientConfig::new(), EnvironmentManager::new("."), ".").unwrap();
+        let request = post("http://127.0.0.1:9", RequestBody::File("missing.json".to_string()));
+        let error = client.execute_request(&request, None).await.unwrap_err();
+        assert!(error.to_string().contains("missing.json"));
+    }
+
     #[test]
     fn test_parse_form_data() {
         let form_data = "name=John+Doe&email=john%40example.com";
@@ -1,7 +1,12 @@
 use anyhow::{Context, Result};
 use reqwest::ClientBuilder;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::cookies::CookieJar;
 use crate::env::{SslConfiguration, CertificateConfig};
+use crate::timing::ConnectionTimer;
+use crate::tls::{self, ClientIdentity};
 
 #[derive(Debug, Clone)]
 pub struct ProxyConfig {
@@ -11,12 +16,68 @@ pub struct ProxyConfig {
     pub password: Option<String>,
 }
 
+/// HTTP version from the request line, e.g. `GET https://example.com HTTP/2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+    /// HTTP/2 negotiated via ALPN, so it requires TLS
+    Http2,
+    /// HTTP/2 without negotiation, also over plain http
+    Http2PriorKnowledge,
+}
+
+impl HttpVersion {
+    pub fn parse(text: &str) -> Result<Self> {
+        let normalized = text.split_whitespace().collect::<Vec<_>>().join(" ").to_uppercase();
+        match normalized.as_str() {
+            "HTTP/1.0" => Ok(HttpVersion::Http10),
+            "HTTP/1.1" => Ok(HttpVersion::Http11),
+            "HTTP/2" | "HTTP/2.0" => Ok(HttpVersion::Http2),
+            "HTTP/2 (PRIOR KNOWLEDGE)" => Ok(HttpVersion::Http2PriorKnowledge),
+            _ => anyhow::bail!(
+                "Unsupported HTTP version: {}; expected HTTP/1.0, HTTP/1.1, HTTP/2 or HTTP/2 (Prior Knowledge)",
+                text
+            ),
+        }
+    }
+
+    /// The version requests are sent with.
+    pub fn version(&self) -> reqwest::Version {
+        match self {
+            HttpVersion::Http10 => reqwest::Version::HTTP_10,
+            HttpVersion::Http11 => reqwest::Version::HTTP_11,
+            HttpVersion::Http2 | HttpVersion::Http2PriorKnowledge => reqwest::Version::HTTP_2,
+        }
+    }
+}
+
+/// Redirects followed before a request fails
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
 #[derive(Debug, Clone)]
 pub struct HttpClientConfig {
     pub proxy: Option<ProxyConfig>,
     pub ssl_config: Option<SslConfiguration>,
     pub verify_certificates: bool,
-    pub http_version: Option<reqwest::Version>,
+    pub http_version: Option<HttpVersion>,
+    /// Client certificate loaded from `ssl_config`, see `with_client_identity`
+    pub client_identity: Option<ClientIdentity>,
+    /// Cookies shared by all requests, see `with_cookie_jar`
+    pub cookie_jar: Option<Arc<CookieJar>>,
+    /// Times DNS lookups of the built clients, see `with_connection_timer`
+    pub connection_timer: Option<ConnectionTimer>,
+    pub follow_redirects: bool,
+    pub max_redirects: usize,
+    pub connect_timeout: Option<Duration>,
+    /// Decompress gzip, deflate, brotli and zstd response bodies
+    pub decompress: bool,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HttpClientConfig {
@@ -26,6 +87,13 @@ impl HttpClientConfig {
             ssl_config: None,
             verify_certificates: true,
             http_version: None,
+            client_identity: None,
+            cookie_jar: None,
+            connection_timer: None,
+            follow_redirects: true,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            connect_timeout: None,
+            decompress: true,
         }
     }
 
@@ -42,11 +110,80 @@ impl HttpClientConfig {
         self
     }
 
-    pub fn with_http_version(mut self, version: reqwest::Version) -> Self {
+    pub fn with_http_version(mut self, version: HttpVersion) -> Self {
         self.http_version = Some(version);
         self
     }
 
+    /// Sets an already loaded client certificate, so a passphrase is asked
+    /// for only once however many clients are built.
+    pub fn with_client_identity(mut self, identity: ClientIdentity) -> Self {
+        self.client_identity = Some(identity);
+        self
+    }
+
+    pub fn with_cookie_jar(mut self, jar: Arc<CookieJar>) -> Self {
+        self.cookie_jar = Some(jar);
+        self
+    }
+
+    /// Resolves host names through `timer`, which all clients built from
+    /// this config share. Requests sent through `ConnectionTimer::send` also
+    /// have their TCP connect and TLS handshake timed.
+    pub fn with_connection_timer(mut self, timer: ConnectionTimer) -> Self {
+        self.connection_timer = Some(timer);
+        self
+    }
+
+    pub fn without_redirects(mut self) -> Self {
+        self.follow_redirects = false;
+        self
+    }
+
+    pub fn without_decompression(mut self) -> Self {
+        self.decompress = false;
+        self
+    }
+
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    fn identity(&self, base_path: &Path) -> Result<Option<ClientIdentity>> {
+        match (&self.client_identity, &self.ssl_config) {
+            (Some(identity), _) => Ok(Some(identity.clone())),
+            (None, Some(ssl_config)) => ClientIdentity::load(ssl_config, base_path),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Builds the TLS connector used by WebSocket and RSocket connections,
+    /// with the same client certificate and trusted roots as HTTP requests.
+    pub fn build_tls_connector(&self, base_path: impl AsRef<Path>) -> Result<native_tls::TlsConnector> {
+        let mut builder = native_tls::TlsConnector::builder();
+        if self.ssl_config.is_some() && !self.verify_certificates {
+            builder.danger_accept_invalid_certs(true);
+        }
+        if let Some(ssl_config) = &self.ssl_config {
+            for der in tls::load_ca_certificates(ssl_config, base_path.as_ref())? {
+                builder.add_root_certificate(native_tls::Certificate::from_der(&der)?);
+            }
+            if ssl_config.use_system_certificates == Some(false) {
+                builder.disable_built_in_roots(true);
+            }
+        }
+        if let Some(identity) = self.identity(base_path.as_ref())? {
+            builder.identity(identity.native_identity()?);
+        }
+        builder.build().context("Failed to build TLS connector")
+    }
+
     pub fn build_client(&self, base_path: impl AsRef<Path>) -> Result<reqwest::Client> {
         let mut builder = ClientBuilder::new();
 
@@ -61,44 +198,49 @@ impl HttpClientConfig {
         }
 
         // Configure SSL/TLS
+        if self.ssl_config.is_some() && !self.verify_certificates {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
         if let Some(ssl_config) = &self.ssl_config {
-            if !self.verify_certificates {
-                builder = builder.danger_accept_invalid_certs(true);
+            for der in tls::load_ca_certificates(ssl_config, base_path.as_ref())? {
+                builder = builder.add_root_certificate(reqwest::Certificate::from_der(&der)?);
             }
-
-            // Load client certificate if provided
-            if let Some(cert_config) = &ssl_config.client_certificate {
-                let cert_path = resolve_cert_path(base_path.as_ref(), cert_config)?;
-                let _cert_data = std::fs::read(&cert_path)
-                    .with_context(|| format!("Failed to read certificate: {:?}", cert_path))?;
-
-                let key_path = ssl_config.client_certificate_key
-                    .as_ref()
-                    .map(|k| resolve_cert_path(base_path.as_ref(), k))
-                    .transpose()?;
-
-                let _key_data = if let Some(kp) = key_path {
-                    Some(std::fs::read(&kp)
-                        .with_context(|| format!("Failed to read key: {:?}", kp))?)
-                } else {
-                    None
-                };
-
-                // Note: reqwest doesn't directly support client certificates in the same way
-                // This would require using rustls directly, which is more complex
-                // For now, we'll skip this and document it as a limitation
+            if ssl_config.use_system_certificates == Some(false) {
+                builder = builder.tls_built_in_root_certs(false);
             }
         }
+        if let Some(identity) = self.identity(base_path.as_ref())? {
+            builder = builder.identity(identity.reqwest_identity()?);
+        }
 
-        // Configure HTTP version
-        // Note: reqwest 0.11 doesn't have direct http_version method
-        // HTTP version is negotiated automatically
+        if let Some(jar) = &self.cookie_jar {
+            builder = builder.cookie_provider(jar.clone());
+        }
+        if let Some(timer) = &self.connection_timer {
+            builder = builder.dns_resolver(Arc::new(timer.clone()));
+        }
+
+        builder = builder.redirect(if self.follow_redirects {
+            reqwest::redirect::Policy::limited(self.max_redirects)
+        } else {
+            reqwest::redirect::Policy::none()
+        });
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        // Configure HTTP version; without one, HTTP/2 is used if ALPN offers it
+        match self.http_version {
+            Some(HttpVersion::Http10 | HttpVersion::Http11) => builder = builder.http1_only(),
+            Some(HttpVersion::Http2PriorKnowledge) => builder = builder.http2_prior_knowledge(),
+            Some(HttpVersion::Http2) | None => {}
+        }
 
         builder.build().context("Failed to build HTTP client")
     }
 }
 
-fn resolve_cert_path(base: &Path, config: &CertificateConfig) -> Result<std::path::PathBuf> {
+pub(crate) fn resolve_cert_path(base: &Path, config: &CertificateConfig) -> Result<std::path::PathBuf> {
     match config {
         CertificateConfig::Path(path) => {
             let path = Path::new(path);
@@ -154,13 +296,48 @@ mod tests {
             client_certificate: None,
             client_certificate_key: None,
             has_certificate_passphrase: None,
+            certificate_passphrase: None,
             verify_host_certificate: Some(false),
+            ca_certificates: None,
+            use_system_certificates: None,
         };
         let config = HttpClientConfig::new().with_ssl_config(ssl_config);
         assert!(config.ssl_config.is_some());
         assert!(!config.verify_certificates);
     }
 
+    #[test]
+    fn test_parse_http_version() {
+        assert_eq!(HttpVersion::parse("HTTP/1.0").unwrap(), HttpVersion::Http10);
+        assert_eq!(HttpVersion::parse("HTTP/1.1").unwrap(), HttpVersion::Http11);
+        assert_eq!(HttpVersion::parse("HTTP/2").unwrap(), HttpVersion::Http2);
+        assert_eq!(
+            HttpVersion::parse("HTTP/2  (Prior Knowledge)").unwrap(),
+            HttpVersion::Http2PriorKnowledge
+        );
+        assert!(HttpVersion::parse("HTTP/3").is_err());
+    }
+
+    #[test]
+    fn test_build_client_for_each_version() {
+        for version in [HttpVersion::Http10, HttpVersion::Http11, HttpVersion::Http2, HttpVersion::Http2PriorKnowledge] {
+            let config = HttpClientConfig::new().with_http_version(version);
+            assert!(config.build_client(".").is_ok());
+        }
+    }
+
+    #[test]
+    fn test_build_with_missing_client_certificate() {
+        let ssl_config = SslConfiguration {
+            client_certificate: Some(CertificateConfig::Path("missing.pem".to_string())),
+            ..SslConfiguration::default()
+        };
+        let config = HttpClientConfig::new().with_ssl_config(ssl_config);
+        assert!(config.build_client(".").is_err());
+        assert!(config.build_tls_connector(".").is_err());
+        assert!(HttpClientConfig::new().build_tls_connector(".").is_ok());
+    }
+
     #[test]
     fn test_resolve_cert_path_absolute() {
         let base = Path::new("/tmp");
@@ -0,0 +1,362 @@
+//! Cookie jar shared by all requests of a run and persisted in IntelliJ's
+//! `http-client.cookies` format: a `# domain\tpath\tname\tvalue\tdate`
+//! header followed by one tab-separated cookie per line, where `date` is the
+//! expiry in RFC 1123 format or `-1` for session cookies. The format has no
+//! `Secure` field, so that flag is only honored until the end of the run.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use reqwest::header::HeaderValue;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use url::Url;
+
+const HEADER: &str = "# domain\tpath\tname\tvalue\tdate";
+const DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredCookie {
+    pub domain: String,
+    pub path: String,
+    pub name: String,
+    pub value: String,
+    /// `None` for session cookies
+    pub expires: Option<DateTime<Utc>>,
+    /// Only sent over https. Not saved, since the file format has no field for it
+    pub secure: bool,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Domain matching also accepts subdomains, since the file format does
+    /// not record whether a cookie was host-only.
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_lowercase();
+        let domain_matches = host == self.domain
+            || host.strip_suffix(&self.domain).is_some_and(|prefix| prefix.ends_with('.'));
+
+        let path = url.path();
+        let path_matches = path == self.path
+            || (path.starts_with(&self.path) && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+
+        domain_matches && path_matches && (!self.secure || url.scheme() == "https")
+    }
+
+    fn parse_line(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [domain, path, name, value, date] = fields.as_slice() else {
+            return None;
+        };
+        let expires = match date.trim() {
+            "-1" | "" => None,
+            date => Some(Utc.from_utc_datetime(&NaiveDateTime::parse_from_str(date, DATE_FORMAT).ok()?)),
+        };
+        Some(Self {
+            domain: domain.to_lowercase(),
+            path: path.to_string(),
+            name: name.to_string(),
+            value: value.to_string(),
+            expires,
+            secure: false,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        let date = match self.expires {
+            Some(expires) => expires.format(DATE_FORMAT).to_string(),
+            None => "-1".to_string(),
+        };
+        format!("{}\t{}\t{}\t{}\t{}", self.domain, self.path, self.name, self.value, date)
+    }
+}
+
+/// Cookie store handed to `reqwest`, optionally backed by a file.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: RwLock<Vec<StoredCookie>>,
+    path: Option<PathBuf>,
+    /// Set when cookies changed since the last save
+    dirty: AtomicBool,
+}
+
+impl CookieJar {
+    /// Creates a jar that is only kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the jar from `path`. A missing file gives an empty jar that is
+    /// created on the first save, and malformed lines are skipped with a
+    /// warning.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut cookies = Vec::new();
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read cookie jar: {}", path.display()))?;
+            let now = Utc::now();
+            for (index, line) in content.lines().enumerate() {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let Some(cookie) = StoredCookie::parse_line(line) else {
+                    eprintln!("Warning: skipping invalid cookie on line {} of {}", index + 1, path.display());
+                    continue;
+                };
+                if !cookie.is_expired(now) {
+                    cookies.push(cookie);
+                }
+            }
+        }
+        Ok(Self {
+            cookies: RwLock::new(cookies),
+            path: Some(path),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn stored_cookies(&self) -> Vec<StoredCookie> {
+        self.cookies.read().unwrap().clone()
+    }
+
+    /// Writes the jar to its file if cookies changed since the last save.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let mut content = format!("{}\n", HEADER);
+        for cookie in self.cookies.read().unwrap().iter().filter(|c| !c.is_expired(now)) {
+            content.push_str(&cookie.to_line());
+            content.push('\n');
+        }
+        std::fs::write(path, content).with_context(|| format!("Failed to save cookie jar: {}", path.display()))
+    }
+
+    /// Stores a `Set-Cookie` value received from `url`. Cookies for another
+    /// domain or a public suffix are ignored, and expired ones remove the
+    /// stored cookie.
+    fn store(&self, set_cookie: &str, url: &Url) {
+        let Ok(cookie) = cookie::Cookie::parse(set_cookie) else {
+            return;
+        };
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        let host = host.to_lowercase();
+
+        let domain = match cookie.domain() {
+            Some(domain) => {
+                let domain = domain.trim_start_matches('.').to_lowercase();
+                if host != domain && (!host.ends_with(&format!(".{}", domain)) || is_public_suffix(&domain)) {
+                    return;
+                }
+                domain
+            }
+            None => host,
+        };
+        let path = match cookie.path() {
+            Some(path) if path.starts_with('/') => path.to_string(),
+            _ => default_path(url.path()),
+        };
+
+        let now = Utc::now();
+        let expires = match (cookie.max_age(), cookie.expires_datetime()) {
+            (Some(max_age), _) => Some(now + chrono::Duration::seconds(max_age.whole_seconds())),
+            (None, Some(expires)) => Utc.timestamp_opt(expires.unix_timestamp(), 0).single(),
+            (None, None) => None,
+        };
+
+        let stored = StoredCookie {
+            domain,
+            path,
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            expires,
+            secure: cookie.secure().unwrap_or(false),
+        };
+
+        let mut cookies = self.cookies.write().unwrap();
+        cookies.retain(|c| !(c.domain == stored.domain && c.path == stored.path && c.name == stored.name));
+        if !stored.is_expired(now) {
+            cookies.push(stored);
+        }
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Second-level labels that registries under country-code TLDs hand out
+/// subdomains of, as in `co.uk` or `com.au`.
+const REGISTRY_LABELS: &[&str] = &["ac", "co", "com", "edu", "gov", "net", "org"];
+
+/// Whether cookies for `domain` would be shared by unrelated sites: a bare
+/// TLD such as `com`, or a registry suffix such as `co.uk`. Approximates the
+/// public suffix list without shipping it.
+fn is_public_suffix(domain: &str) -> bool {
+    match domain.split('.').collect::<Vec<_>>().as_slice() {
+        [_] => true,
+        [second, tld] => tld.len() == 2 && REGISTRY_LABELS.contains(second),
+        _ => false,
+    }
+}
+
+/// The directory of the request path, per RFC 6265 section 5.1.4.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(pos) => path[..pos].to_string(),
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        for header in cookie_headers {
+            if let Ok(value) = header.to_str() {
+                self.store(value, url);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let now = Utc::now();
+        let cookies = self.cookies.read().unwrap();
+        let mut matching: Vec<&StoredCookie> =
+            cookies.iter().filter(|c| !c.is_expired(now) && c.matches(url)).collect();
+        // Cookies with longer paths are listed first
+        matching.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+
+        let value = matching
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if value.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&value).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::cookie::CookieStore;
+
+    fn set(jar: &CookieJar, url: &str, header: &str) {
+        let value = HeaderValue::from_str(header).unwrap();
+        jar.set_cookies(&mut std::iter::once(&value), &Url::parse(url).unwrap());
+    }
+
+    fn header(jar: &CookieJar, url: &str) -> Option<String> {
+        jar.cookies(&Url::parse(url).unwrap()).map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_domain_and_path_matching() {
+        let jar = CookieJar::new();
+        set(&jar, "http://example.com/api/login", "session=abc");
+        set(&jar, "http://example.com/", "theme=dark; Path=/; Domain=.example.com");
+        set(&jar, "http://example.com/", "other=x; Domain=other.com");
+
+        assert_eq!(header(&jar, "http://example.com/api/users").as_deref(), Some("session=abc; theme=dark"));
+        assert_eq!(header(&jar, "http://api.example.com/").as_deref(), Some("theme=dark"));
+        assert_eq!(header(&jar, "http://example.com/apiv2").as_deref(), Some("theme=dark"));
+        assert_eq!(header(&jar, "http://other.com/"), None);
+    }
+
+    #[test]
+    fn test_expired_cookie_removes_stored_one() {
+        let jar = CookieJar::new();
+        set(&jar, "http://localhost/", "session=abc");
+        set(&jar, "http://localhost/", "session=; Max-Age=0");
+        assert_eq!(header(&jar, "http://localhost/"), None);
+        assert!(jar.stored_cookies().is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("http-client.cookies");
+
+        let jar = CookieJar::load(&path).unwrap();
+        assert!(jar.stored_cookies().is_empty());
+        set(&jar, "http://localhost/", "session=abc");
+        set(&jar, "http://localhost/", "remember=1; Expires=Fri, 01 Jan 2100 00:00:00 GMT");
+        jar.save().unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            "# domain\tpath\tname\tvalue\tdate\n\
+             localhost\t/\tsession\tabc\t-1\n\
+             localhost\t/\tremember\t1\tFri, 01 Jan 2100 00:00:00 GMT\n"
+        );
+
+        let loaded = CookieJar::load(&path).unwrap();
+        assert_eq!(loaded.stored_cookies(), jar.stored_cookies());
+        assert_eq!(header(&loaded, "http://localhost/x").as_deref(), Some("session=abc; remember=1"));
+    }
+
+    #[test]
+    fn test_load_skips_expired_and_invalid_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("http-client.cookies");
+        std::fs::write(&path, format!("{}\nlocalhost\t/\told\t1\tThu, 01 Jan 2015 00:00:00 GMT\n", HEADER)).unwrap();
+        assert!(CookieJar::load(&path).unwrap().stored_cookies().is_empty());
+
+        std::fs::write(&path, "localhost\t/\tmissing-fields\nlocalhost\t/\tsession\tabc\t-1\n").unwrap();
+        let jar = CookieJar::load(&path).unwrap();
+        assert_eq!(header(&jar, "http://localhost/").as_deref(), Some("session=abc"));
+    }
+
+    #[test]
+    fn test_secure_cookies_are_only_sent_over_https() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("http-client.cookies");
+
+        let jar = CookieJar::load(&path).unwrap();
+        set(&jar, "https://localhost/", "session=abc; Secure");
+        set(&jar, "https://localhost/", "theme=dark");
+        assert_eq!(header(&jar, "https://localhost/").as_deref(), Some("session=abc; theme=dark"));
+        assert_eq!(header(&jar, "http://localhost/").as_deref(), Some("theme=dark"));
+
+
+        // The saved file keeps IntelliJ's five columns
+        jar.save().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("localhost\t/\tsession\tabc\t-1\n"), "{}", content);
+        assert!(content.lines().all(|line| line.split('\t').count() == 5), "{}", content);
+        std::fs::write(&path, "localhost\t/\tsession\tabc\t-1\tsecure\n").unwrap();
+        assert!(CookieJar::load(&path).unwrap().stored_cookies().is_empty());
+    }
+
+    #[test]
+    fn test_public_suffix_domains_are_ignored() {
+        let jar = CookieJar::new();
+        set(&jar, "http://api.example.com/", "tld=1; Domain=com");
+        set(&jar, "http://api.example.co.uk/", "registry=1; Domain=.co.uk");
+        set(&jar, "http://api.example.co.uk/", "site=1; Domain=example.co.uk");
+        assert_eq!(header(&jar, "http://other.com/"), None);
+        assert_eq!(header(&jar, "http://other.co.uk/"), None);
+        assert_eq!(header(&jar, "http://www.example.co.uk/").as_deref(), Some("site=1"));
+
+        // A host may still set a cookie for itself
+        set(&jar, "http://localhost/", "session=abc; Domain=localhost");
+        assert_eq!(header(&jar, "http://localhost/").as_deref(), Some("session=abc"));
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::Result;
 use regex::Regex;
-use std::collections::HashMap;
+use crate::headers::Headers;
+use crate::parser::{HttpRequest, Request, RequestBody, RequestOptions, Span};
+use crate::writer;
 
 pub struct CurlConverter;
 
@@ -8,7 +10,7 @@ impl CurlConverter {
     pub fn curl_to_http(curl_command: &str) -> Result<String> {
         let mut method = "GET".to_string();
         let mut url = String::new();
-        let mut headers = HashMap::new();
+        let mut headers = Headers::new();
         let mut body: Option<String> = None;
 
         // Remove 'curl' prefix and clean up
@@ -33,7 +35,7 @@ impl CurlConverter {
             if let Some(colon_pos) = header.find(':') {
                 let key = header[..colon_pos].trim().to_string();
                 let value = header[colon_pos + 1..].trim().to_string();
-                headers.insert(key, value);
+                headers.append(key, value);
             }
         }
 
@@ -57,23 +59,24 @@ impl CurlConverter {
             }
         }
 
-        // Build HTTP request format
-        let mut result = format!("# Converted from cURL\n");
-        result.push_str(&format!("###\n"));
-        result.push_str(&format!("{} {}\n", method, url));
-
-        for (key, value) in &headers {
-            result.push_str(&format!("{}: {}\n", key, value));
-        }
-
-        if let Some(body_content) = body {
-            if !headers.is_empty() {
-                result.push_str("\n");
-            }
-            result.push_str(&format!("{}\n", body_content));
-        }
-
-        Ok(result)
+        let request = HttpRequest {
+            name: None,
+            method,
+            uri: url,
+            http_version: None,
+            headers,
+            body: body.map(RequestBody::Inline),
+            comments: vec!["# Converted from cURL".to_string()],
+            header_comments: Vec::new(),
+            pre_request_scripts: Vec::new(),
+            response_handlers: Vec::new(),
+            assertions: Vec::new(),
+            options: RequestOptions::default(),
+            output: None,
+            tags: Vec::new(),
+            span: Span::default(),
+        };
+        Ok(writer::write_request(&Request::Http(request)))
     }
 
     pub fn http_to_curl(request: &str) -> Result<String> {
@@ -173,6 +176,24 @@ mod tests {
         assert!(http.contains("Authorization: Bearer token123"));
     }
 
+    #[test]
+    fn test_curl_to_http_keeps_header_order() {
+        let curl = "curl 'https://api.example.com/' -H 'X-B: 2' -H 'Accept: text/html' -H 'X-A: 1' -H 'Accept: application/json'";
+        let http = CurlConverter::curl_to_http(curl).unwrap();
+        assert!(http.contains("X-B: 2\nAccept: text/html\nX-A: 1\nAccept: application/json\n"));
+    }
+
+    #[test]
+    fn test_curl_to_http_parses_back() {
+        let curl = "curl 'https://api.example.com/users' -d 'name=John'";
+        let http = CurlConverter::curl_to_http(curl).unwrap();
+        let requests = crate::parser::HttpFileParser::new(http).parse().unwrap();
+        let Request::Http(request) = &requests[0] else { panic!("expected an HTTP request") };
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.uri, "https://api.example.com/users");
+        assert_eq!(request.body, Some(RequestBody::Inline("name=John".to_string())));
+    }
+
     #[test]
     fn test_http_to_curl_get() {
         let http = r###"
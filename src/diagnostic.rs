@@ -0,0 +1,94 @@
+//! Problems found while parsing a `.http` file, with their position.
+
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The file can't be run as written
+    Error,
+    /// Something is ignored that probably wasn't meant to be
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Empty when the content was not read from a file
+    pub file: PathBuf,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Formats the diagnostic like a compiler does, followed by the offending
+    /// line of `source` with a marker under the column.
+    pub fn render(&self, source: &str) -> String {
+        let mut rendered = self.to_string();
+        if let Some(text) = source.lines().nth(self.line - 1) {
+            let gutter = self.line.to_string().len();
+            rendered.push_str(&format!("\n{:>gutter$} |\n", ""));
+            rendered.push_str(&format!("{} | {}\n", self.line, text));
+            rendered.push_str(&format!("{:>gutter$} | {:>column$}", "", "^", column = self.column));
+        }
+        rendered
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.file.as_os_str().is_empty() {
+            write!(f, "line {}, column {}", self.line, self.column)?;
+        } else {
+            write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)?;
+        }
+        write!(f, ": {}: {}", self.severity, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(file: &str) -> Diagnostic {
+        Diagnostic {
+            file: PathBuf::from(file),
+            line: 3,
+            column: 14,
+            severity: Severity::Error,
+            message: "Expected a header `Name: value`".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(diagnostic("api.http").to_string(), "api.http:3:14: error: Expected a header `Name: value`");
+        assert_eq!(diagnostic("").to_string(), "line 3, column 14: error: Expected a header `Name: value`");
+    }
+
+    #[test]
+    fn test_render_points_at_column() {
+        let source = "### Users\nGET https://example.com\nAuthorization Bearer x\n";
+        let mut diagnostic = diagnostic("api.http");
+        diagnostic.column = 15;
+        assert_eq!(
+            diagnostic.render(source),
+            "api.http:3:15: error: Expected a header `Name: value`\n  |\n3 | Authorization Bearer x\n  |               ^"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Dynamic variables such as `{{$uuid}}`, `{{$timestamp}}` or
+//! `{{$random.alphabetic(8)}}`, evaluated fresh for every occurrence.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Mutex;
+
+const ALPHABETIC: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+const HEXADECIMAL: &[u8] = b"0123456789abcdef";
+const NUMERIC: &[u8] = b"0123456789";
+
+pub struct DynamicVariables {
+    rng: Mutex<StdRng>,
+    seed: u64,
+    /// Frozen clock; `None` uses the current time
+    now: Option<DateTime<Utc>>,
+}
+
+impl Default for DynamicVariables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DynamicVariables {
+    pub fn new() -> Self {
+        Self::with_seed(rand::random(), None)
+    }
+
+    /// Creates a provider whose random values are derived from `seed` and
+    /// whose timestamps are frozen at `now`, if given, so runs can be replayed.
+    pub fn with_seed(seed: u64, now: Option<DateTime<Utc>>) -> Self {
+        Self {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            seed,
+            now,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn frozen_now(&self) -> Option<DateTime<Utc>> {
+        self.now
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.now.unwrap_or_else(Utc::now)
+    }
+
+    /// Evaluates a dynamic variable given without its leading `$`, e.g.
+    /// `uuid` or `random.integer(1, 10)`. Returns `None` for unknown names
+    /// or invalid arguments.
+    pub fn resolve(&self, expression: &str) -> Option<String> {
+        let (name, args) = parse_call(expression)?;
+        let mut rng = self.rng.lock().unwrap();
+
+        let value = match (name, args.as_slice()) {
+            ("uuid" | "random.uuid", []) => {
+                uuid::Builder::from_random_bytes(rng.gen()).into_uuid().to_string()
+            }
+            ("timestamp", []) => self.now().timestamp().to_string(),
+            ("isoTimestamp", []) => self.now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            ("randomInt", []) => rng.gen_range(0..=1000).to_string(),
+            ("random.integer", []) => rng.gen_range(0..=1000).to_string(),
+            ("random.integer", [from, to]) => {
+                let (from, to): (i64, i64) = (from.parse().ok()?, to.parse().ok()?);
+                if from >= to {
+                    return None;
+                }
+                rng.gen_range(from..to).to_string()
+            }
+            ("random.float", []) => rng.gen_range(0.0..1000.0).to_string(),
+            ("random.float", [from, to]) => {
+                let (from, to): (f64, f64) = (from.parse().ok()?, to.parse().ok()?);
+                if from >= to {
+                    return None;
+                }
+                rng.gen_range(from..to).to_string()
+            }
+            ("random.alphabetic", [length]) => random_string(&mut *rng, ALPHABETIC, length.parse().ok()?),
+            ("random.alphanumeric", [length]) => random_string(&mut *rng, ALPHANUMERIC, length.parse().ok()?),
+            ("random.hexadecimal", [length]) => random_string(&mut *rng, HEXADECIMAL, length.parse().ok()?),
+            ("random.numeric", [length]) => random_string(&mut *rng, NUMERIC, length.parse().ok()?),
+            ("random.email", []) => format!(
+                "{}@{}.com",
+                random_string(&mut *rng, ALPHANUMERIC, 10).to_lowercase(),
+                random_string(&mut *rng, ALPHABETIC, 8).to_lowercase()
+            ),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+}
+
+/// Splits `name(arg1, arg2)` into the name and trimmed arguments.
+fn parse_call(expression: &str) -> Option<(&str, Vec<&str>)> {
+    let expression = expression.trim();
+    let Some(open) = expression.find('(') else {
+        return Some((expression, Vec::new()));
+    };
+
+    let name = expression[..open].trim();
+    let inner = expression[open + 1..].strip_suffix(')')?.trim();
+    let args = if inner.is_empty() {
+        Vec::new()
+    } else {
+        inner.split(',').map(|arg| arg.trim().trim_matches(|c| c == '"' || c == '\'')).collect()
+    };
+
+    Some((name, args))
+}
+
+fn random_string(rng: &mut impl Rng, charset: &[u8], length: usize) -> String {
+    (0..length)
+        .map(|_| charset[rng.gen_range(0..charset.len())] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid() {
+        let dynamic = DynamicVariables::new();
+        let first = dynamic.resolve("uuid").unwrap();
+        let second = dynamic.resolve("random.uuid").unwrap();
+
+        assert!(uuid::Uuid::parse_str(&first).is_ok());
+        assert_eq!(uuid::Uuid::parse_str(&first).unwrap().get_version_num(), 4);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let dynamic = DynamicVariables::new();
+        let timestamp: i64 = dynamic.resolve("timestamp").unwrap().parse().unwrap();
+        assert!(timestamp > 1_600_000_000);
+
+        let iso = dynamic.resolve("isoTimestamp").unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(&iso).is_ok());
+        assert!(iso.ends_with('Z'));
+    }
+
+    #[test]
+    fn test_random_numbers() {
+        let dynamic = DynamicVariables::new();
+        let value: i64 = dynamic.resolve("randomInt").unwrap().parse().unwrap();
+        assert!((0..=1000).contains(&value));
+
+        let value: i64 = dynamic.resolve("random.integer(5, 7)").unwrap().parse().unwrap();
+        assert!((5..7).contains(&value));
+
+        let value: f64 = dynamic.resolve("random.float(0.5, 1.5)").unwrap().parse().unwrap();
+        assert!((0.5..1.5).contains(&value));
+    }
+
+    #[test]
+    fn test_random_strings() {
+        let dynamic = DynamicVariables::new();
+
+        let alphabetic = dynamic.resolve("random.alphabetic(12)").unwrap();
+        assert_eq!(alphabetic.len(), 12);
+        assert!(alphabetic.chars().all(|c| c.is_ascii_alphabetic()));
+
+        let hex = dynamic.resolve("random.hexadecimal(8)").unwrap();
+        assert_eq!(hex.len(), 8);
+        assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let numeric = dynamic.resolve("random.numeric(4)").unwrap();
+        assert!(numeric.chars().all(|c| c.is_ascii_digit()));
+
+        let email = dynamic.resolve("random.email").unwrap();
+        assert!(email.contains('@') && email.ends_with(".com"));
+    }
+
+    #[test]
+    fn test_seeded_values_are_reproducible() {
+        let expressions = ["uuid", "randomInt", "random.alphanumeric(16)", "random.email", "random.float(0, 1)"];
+        let first = DynamicVariables::with_seed(42, None);
+        let second = DynamicVariables::with_seed(42, None);
+        let other = DynamicVariables::with_seed(43, None);
+
+        let run = |dynamic: &DynamicVariables| -> Vec<String> {
+            expressions.iter().map(|e| dynamic.resolve(e).unwrap()).collect()
+        };
+        let values = run(&first);
+        assert_eq!(values, run(&second));
+        assert_ne!(values, run(&other));
+        assert_eq!(first.seed(), 42);
+    }
+
+    #[test]
+    fn test_frozen_clock() {
+        let now = DateTime::parse_from_rfc3339("2024-05-01T12:30:00Z").unwrap().with_timezone(&Utc);
+        let dynamic = DynamicVariables::with_seed(1, Some(now));
+
+        assert_eq!(dynamic.resolve("timestamp").unwrap(), "1714566600");
+        assert_eq!(dynamic.resolve("isoTimestamp").unwrap(), "2024-05-01T12:30:00.000Z");
+        assert_eq!(dynamic.frozen_now(), Some(now));
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        let dynamic = DynamicVariables::new();
+        assert_eq!(dynamic.resolve("unknown"), None);
+        assert_eq!(dynamic.resolve("random.alphabetic"), None);
+        assert_eq!(dynamic.resolve("random.alphabetic(abc)"), None);
+        assert_eq!(dynamic.resolve("random.integer(10, 1)"), None);
+        assert_eq!(dynamic.resolve("random.integer(1, 10"), None);
+    }
+}
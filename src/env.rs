@@ -2,17 +2,22 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use anyhow::{Context, Result};
+use crate::chain;
+use crate::client::HttpResponse;
+use crate::dynamic::DynamicVariables;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Environment {
     #[serde(flatten)]
     pub variables: HashMap<String, serde_json::Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "SSLConfiguration", alias = "ssl_config", skip_serializing_if = "Option::is_none")]
     pub ssl_config: Option<SslConfiguration>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SslConfiguration {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_certificate: Option<CertificateConfig>,
@@ -20,8 +25,17 @@ pub struct SslConfiguration {
     pub client_certificate_key: Option<CertificateConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub has_certificate_passphrase: Option<bool>,
+    /// Passphrase for the key or PKCS#12 bundle; keep it in the private env file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificate_passphrase: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub verify_host_certificate: Option<bool>,
+    /// Extra trusted root certificates: a PEM or DER file, or a directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_certificates: Option<String>,
+    /// Whether the system trust store is used as well; defaults to true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_system_certificates: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,15 +52,35 @@ pub enum CertificateConfig {
 pub struct EnvironmentManager {
     environments: HashMap<String, Environment>,
     private_env_path: Option<PathBuf>,
+    /// String values from the private env file, per environment
+    secrets: HashMap<String, Vec<String>>,
+    #[allow(dead_code)]
     base_path: PathBuf,
+    /// Variables set by scripts via `client.global`, shared by all clones.
+    globals: Arc<RwLock<HashMap<String, String>>>,
+    /// Responses of named requests executed so far in this run.
+    responses: Arc<RwLock<HashMap<String, HttpResponse>>>,
+    /// In-file `@name = value` variables seen by the request being run,
+    /// shared by all clones.
+    file_variables: Arc<RwLock<HashMap<String, String>>>,
+    /// Provider for `{{$uuid}}`, `{{$random.*}}` and friends.
+    dynamic: Arc<DynamicVariables>,
 }
 
+/// Limit for file variables that reference other variables, to stop cycles.
+const MAX_RESOLVE_DEPTH: usize = 10;
+
 impl EnvironmentManager {
     pub fn new(base_path: impl AsRef<Path>) -> Self {
         Self {
             environments: HashMap::new(),
             private_env_path: None,
+            secrets: HashMap::new(),
             base_path: base_path.as_ref().to_path_buf(),
+            globals: Arc::new(RwLock::new(HashMap::new())),
+            responses: Arc::new(RwLock::new(HashMap::new())),
+            file_variables: Arc::new(RwLock::new(HashMap::new())),
+            dynamic: Arc::new(DynamicVariables::new()),
         }
     }
 
@@ -57,7 +91,15 @@ impl EnvironmentManager {
                 .with_context(|| format!("Failed to read private env file: {:?}", path))?;
             let envs: HashMap<String, Environment> = serde_json::from_str(&content)
                 .with_context(|| "Failed to parse private env file")?;
-            
+
+            for (name, env) in &envs {
+                let passphrase = env.ssl_config.as_ref().and_then(|ssl| ssl.certificate_passphrase.clone());
+                let secrets = self.secrets.entry(name.clone()).or_default();
+                for value in env.variables.values() {
+                    string_values(value, secrets);
+                }
+                secrets.extend(passphrase);
+            }
             self.environments.extend(envs);
             self.private_env_path = Some(path.to_path_buf());
         }
@@ -86,11 +128,64 @@ impl EnvironmentManager {
         Ok(())
     }
 
+    /// String values from the private env file for `env_name`, including
+    /// those nested in objects and arrays, to keep out of reports.
+    pub fn secrets(&self, env_name: &str) -> Vec<String> {
+        self.secrets
+            .get(env_name)
+            .into_iter()
+            .flatten()
+            .filter(|value| !value.is_empty())
+            .cloned()
+            .collect()
+    }
+
     pub fn get_environment(&self, name: &str) -> Option<&Environment> {
         self.environments.get(name)
     }
 
+    /// Sets the in-file variables for the next request, see
+    /// `HttpFile::variables_for`. Later declarations of a name win.
+    pub fn set_file_variables(&self, variables: &[(String, String)]) {
+        *self.file_variables.write().unwrap() = variables.iter().cloned().collect();
+    }
+
+    /// Replaces the dynamic variable provider, e.g. with a seeded one shared
+    /// by several managers.
+    pub fn set_dynamic_variables(&mut self, dynamic: impl Into<Arc<DynamicVariables>>) {
+        self.dynamic = dynamic.into();
+    }
+
+    pub fn dynamic_variables(&self) -> &DynamicVariables {
+        &self.dynamic
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<String> {
+        self.globals.read().unwrap().get(name).cloned()
+    }
+
+    pub fn set_global(&self, name: impl Into<String>, value: impl Into<String>) {
+        self.globals.write().unwrap().insert(name.into(), value.into());
+    }
+
+    pub fn globals(&self) -> HashMap<String, String> {
+        self.globals.read().unwrap().clone()
+    }
+
+    pub fn replace_globals(&self, globals: HashMap<String, String>) {
+        *self.globals.write().unwrap() = globals;
+    }
+
+    /// Makes `response` available to later requests as `{{name.response...}}`.
+    pub fn record_response(&self, name: impl Into<String>, response: HttpResponse) {
+        self.responses.write().unwrap().insert(name.into(), response);
+    }
+
     pub fn resolve_variable(&self, env_name: &str, var_name: &str) -> Option<String> {
+        if let Some(value) = self.get_global(var_name) {
+            return Some(value);
+        }
+
         self.environments
             .get(env_name)
             .and_then(|env| env.variables.get(var_name))
@@ -103,13 +198,49 @@ impl EnvironmentManager {
     }
 
     pub fn resolve_string(&self, env_name: &str, text: &str) -> String {
+        self.resolve_string_with(env_name, text, &HashMap::new())
+    }
+
+    /// Like `resolve_string`, but `variables` (e.g. set by a pre-request
+    /// script) take precedence over in-file variables, globals and the
+    /// environment.
+    pub fn resolve_string_with(
+        &self,
+        env_name: &str,
+        text: &str,
+        variables: &HashMap<String, String>,
+    ) -> String {
+        self.resolve_with_depth(env_name, text, variables, 0)
+    }
+
+    fn resolve_with_depth(
+        &self,
+        env_name: &str,
+        text: &str,
+        variables: &HashMap<String, String>,
+        depth: usize,
+    ) -> String {
         let mut result = text.to_string();
         
         // Replace {{variable}} patterns
         let re = regex::Regex::new(r"\{\{([^}]+)\}\}").unwrap();
         result = re.replace_all(&result, |caps: &regex::Captures| {
             let var_name = caps.get(1).unwrap().as_str().trim();
-            self.resolve_variable(env_name, var_name)
+            if let Some(value) = var_name.strip_prefix('$').and_then(|e| self.dynamic.resolve(e)) {
+                return value;
+            }
+            variables
+                .get(var_name)
+                .cloned()
+                .or_else(|| {
+                    // In-file variables may reference other variables
+                    let value = self.file_variables.read().unwrap().get(var_name).cloned();
+                    value
+                        .filter(|_| depth < MAX_RESOLVE_DEPTH)
+                        .map(|value| self.resolve_with_depth(env_name, &value, variables, depth + 1))
+                })
+                .or_else(|| self.resolve_variable(env_name, var_name))
+                .or_else(|| chain::resolve_reference(&self.responses.read().unwrap(), var_name))
                 .unwrap_or_else(|| caps.get(0).unwrap().as_str().to_string())
         }).to_string();
         
@@ -123,9 +254,22 @@ impl EnvironmentManager {
     }
 }
 
+/// Collects the strings in `value` and anything nested in it.
+fn string_values(value: &serde_json::Value, out: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(text) => out.push(text.clone()),
+        serde_json::Value::Array(items) => items.iter().for_each(|item| string_values(item, out)),
+        serde_json::Value::Object(fields) => fields.values().for_each(|field| string_values(field, out)),
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::body::ResponseBody;
+    use crate::headers::Headers;
+    use crate::timing::Timings;
 
     #[test]
     fn test_variable_resolution() {
@@ -160,7 +304,7 @@ mod tests {
     #[test]
     fn test_resolve_string_with_unknown_variable() {
         let mut manager = EnvironmentManager::new(".");
-        let mut env = Environment {
+        let env = Environment {
             variables: HashMap::new(),
             ssl_config: None,
         };
@@ -210,6 +354,124 @@ mod tests {
         assert_eq!(result, "https://api.example.com/users");
     }
 
+    #[test]
+    fn test_globals_shared_between_clones() {
+        let manager = EnvironmentManager::new(".");
+        let clone = manager.clone();
+        clone.set_global("token", "abc");
+
+        assert_eq!(manager.get_global("token"), Some("abc".to_string()));
+        assert_eq!(manager.resolve_string("dev", "Bearer {{token}}"), "Bearer abc");
+    }
+
+    #[test]
+    fn test_global_overrides_environment() {
+        let mut manager = EnvironmentManager::new(".");
+        let mut env = Environment {
+            variables: HashMap::new(),
+            ssl_config: None,
+        };
+        env.variables.insert("token".to_string(), serde_json::Value::String("from-env".to_string()));
+        manager.environments.insert("dev".to_string(), env);
+        manager.set_global("token", "from-script");
+
+        assert_eq!(manager.resolve_variable("dev", "token"), Some("from-script".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_string_with_request_variables() {
+        let manager = EnvironmentManager::new(".");
+        manager.set_global("ts", "global");
+        let variables = HashMap::from([("ts".to_string(), "1700000000".to_string())]);
+
+        let result = manager.resolve_string_with("dev", "/orders?ts={{ts}}&x={{missing}}", &variables);
+        assert_eq!(result, "/orders?ts=1700000000&x={{missing}}");
+    }
+
+    #[test]
+    fn test_resolve_string_with_response_reference() {
+        let manager = EnvironmentManager::new(".");
+        manager.clone().record_response(
+            "login",
+            HttpResponse {
+                request: Default::default(),
+                status: 200,
+                version: reqwest::Version::HTTP_11,
+                redirects: Vec::new(),
+                saved_to: None,
+                timings: Timings::default(),
+                headers: Headers::new(),
+                body: ResponseBody::from(r#"{"token": "abc"}"#),
+                content_type: Some("application/json".to_string()),
+                duration: std::time::Duration::ZERO,
+                tests: Vec::new(),
+                logs: Vec::new(),
+            },
+        );
+
+        let result = manager.resolve_string("dev", "Bearer {{login.response.body.$.token}}");
+        assert_eq!(result, "Bearer abc");
+    }
+
+    #[test]
+    fn test_file_variables_reference_env_and_each_other() {
+        let mut manager = EnvironmentManager::new(".");
+        let mut env = Environment {
+            variables: HashMap::new(),
+            ssl_config: None,
+        };
+        env.variables.insert("host".to_string(), serde_json::Value::String("https://env.example.com".to_string()));
+        env.variables.insert("version".to_string(), serde_json::Value::String("v1".to_string()));
+        manager.environments.insert("dev".to_string(), env);
+        manager.set_file_variables(&[
+            ("host".to_string(), "https://file.example.com".to_string()),
+            ("base".to_string(), "{{host}}/{{version}}".to_string()),
+        ]);
+
+        assert_eq!(manager.resolve_string("dev", "{{base}}/users"), "https://file.example.com/v1/users");
+    }
+
+    #[test]
+    fn test_file_variable_cycle_terminates() {
+        let manager = EnvironmentManager::new(".");
+        manager.set_file_variables(&[
+            ("a".to_string(), "{{b}}".to_string()),
+            ("b".to_string(), "{{a}}".to_string()),
+        ]);
+
+        let result = manager.resolve_string("dev", "{{a}}");
+        assert!(result.contains("{{"));
+    }
+
+    #[test]
+    fn test_dynamic_variables_evaluated_per_occurrence() {
+        let manager = EnvironmentManager::new(".");
+        let result = manager.resolve_string("dev", "{{$uuid}} {{$uuid}} {{$random.alphabetic(5)}} {{$nope}}");
+        let parts: Vec<&str> = result.split(' ').collect();
+
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0].len(), 36);
+        assert_ne!(parts[0], parts[1]);
+        assert_eq!(parts[2].len(), 5);
+        assert_eq!(parts[3], "{{$nope}}");
+    }
+
+    #[test]
+    fn test_seeded_dynamic_variables_are_shared_between_clones() {
+        let template = "{{$uuid}} {{$random.integer(1, 100)}}";
+        let mut first = EnvironmentManager::new(".");
+        first.set_dynamic_variables(DynamicVariables::with_seed(7, None));
+        let mut second = EnvironmentManager::new(".");
+        second.set_dynamic_variables(DynamicVariables::with_seed(7, None));
+
+        // Clones draw from the same sequence, so the second value differs
+        let clone = first.clone();
+        let values = (first.resolve_string("dev", template), clone.resolve_string("dev", template));
+        assert_eq!(values.0, second.resolve_string("dev", template));
+        assert_eq!(values.1, second.resolve_string("dev", template));
+        assert_ne!(values.0, values.1);
+    }
+
     #[test]
     fn test_get_ssl_config() {
         let mut manager = EnvironmentManager::new(".");
@@ -217,9 +479,12 @@ mod tests {
             client_certificate: Some(CertificateConfig::Path("cert.pem".to_string())),
             client_certificate_key: None,
             has_certificate_passphrase: Some(true),
+            certificate_passphrase: None,
             verify_host_certificate: Some(false),
+            ca_certificates: None,
+            use_system_certificates: None,
         };
-        let mut env = Environment {
+        let env = Environment {
             variables: HashMap::new(),
             ssl_config: Some(ssl_config.clone()),
         };
@@ -229,4 +494,72 @@ mod tests {
         assert!(config.is_some());
         assert_eq!(config.unwrap().verify_host_certificate, Some(false));
     }
+
+    #[test]
+    fn test_load_ssl_configuration_from_private_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rest-client.private.env.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "dev": {
+                    "token": "secret",
+                    "SSLConfiguration": {
+                        "clientCertificate": {"path": "client.p12", "format": "PKCS12"},
+                        "hasCertificatePassphrase": true,
+                        "certificatePassphrase": "changeit",
+                        "verifyHostCertificate": false
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let mut manager = EnvironmentManager::new(dir.path());
+        manager.load_private_env(&path).unwrap();
+        let config = manager.get_ssl_config("dev").unwrap();
+
+        assert!(matches!(
+            &config.client_certificate,
+            Some(CertificateConfig::Detailed { path, format: Some(format) }) if path == "client.p12" && format == "PKCS12"
+        ));
+        assert_eq!(config.has_certificate_passphrase, Some(true));
+        assert_eq!(config.certificate_passphrase.as_deref(), Some("changeit"));
+        assert_eq!(config.verify_host_certificate, Some(false));
+        assert_eq!(manager.resolve_variable("dev", "token"), Some("secret".to_string()));
+    }
+
+    #[test]
+    fn test_secrets_from_private_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let private = dir.path().join("rest-client.private.env.json");
+        std::fs::write(
+            &private,
+            r#"{
+                "dev": {
+                    "token": "s3cr3t-token",
+                    "pin": "123",
+                    "empty": "",
+                    "retries": 3,
+                    "oauth": {"client": {"secret": "nested-secret"}, "scopes": ["admin"]},
+                    "SSLConfiguration": {"certificatePassphrase": "changeit"}
+                },
+                "prod": {"token": "prod-token"}
+            }"#,
+        )
+        .unwrap();
+        let public = dir.path().join("http-client.env.json");
+        std::fs::write(&public, r#"{"dev": {"host": "localhost"}}"#).unwrap();
+
+        let mut manager = EnvironmentManager::new(dir.path());
+        manager.load_private_env(&private).unwrap();
+        manager.load_env_file(&public).unwrap();
+
+        let mut secrets = manager.secrets("dev");
+        secrets.sort();
+        // Empty values and numbers are left out; public values are not secret
+        assert_eq!(secrets, ["123", "admin", "changeit", "nested-secret", "s3cr3t-token"]);
+        assert_eq!(manager.secrets("prod"), ["prod-token"]);
+        assert!(manager.secrets("staging").is_empty());
+    }
 }
@@ -1,13 +1,28 @@
 use anyhow::{Context, Result};
 use reqwest::Client;
 use serde_json::json;
+use std::time::Instant;
 use url::Url;
+use crate::client::SentRequest;
 use crate::env::EnvironmentManager;
+use crate::headers::Headers;
 use crate::parser::GraphQLRequest;
+use crate::timing::{self, ConnectionTimer, Timings};
+
+#[derive(Debug, Clone)]
+pub struct GraphQLResponse {
+    pub request: SentRequest,
+    pub status: u16,
+    pub headers: Headers,
+    pub body: String,
+    pub timings: Timings,
+}
 
 pub struct GraphQLClient {
     client: Client,
     env_manager: EnvironmentManager,
+    /// Resolver of `client`; without one connecting is not reported
+    connection_timer: Option<ConnectionTimer>,
 }
 
 impl GraphQLClient {
@@ -15,14 +30,22 @@ impl GraphQLClient {
         Self {
             client,
             env_manager,
+            connection_timer: None,
         }
     }
 
+    /// Reports the connections timed by `timer`, which must be the resolver
+    /// of the client, see `HttpClientConfig::with_connection_timer`.
+    pub fn with_connection_timer(mut self, timer: ConnectionTimer) -> Self {
+        self.connection_timer = Some(timer);
+        self
+    }
+
     pub async fn execute_request(
         &self,
         request: &GraphQLRequest,
         env_name: Option<&str>,
-    ) -> Result<String> {
+    ) -> Result<GraphQLResponse> {
         let env_name = env_name.unwrap_or("default");
 
         // Resolve URI with environment variables
@@ -59,23 +82,49 @@ impl GraphQLClient {
         }
 
         // Default Content-Type if not specified
-        if !request.headers.contains_key("Content-Type") && 
-           !request.headers.contains_key("content-type") {
+        if !request.headers.contains_key("Content-Type") {
             req_builder = req_builder.header("Content-Type", "application/json");
         }
 
         // Execute request
-        let response = req_builder
+        let request = req_builder
             .json(&body)
-            .send()
-            .await
-            .context("Failed to send GraphQL request")?;
+            .build()
+            .context("Failed to build GraphQL request")?;
+        if let Some(timer) = &self.connection_timer {
+            timer.take();
+        }
+        let request_size = timing::request_size(&request);
+        let sent = SentRequest::from(&request);
+        let started = Instant::now();
+        let tls = request.url().scheme() == "https";
+        let response = match &self.connection_timer {
+            Some(timer) => timer.send(tls, self.client.execute(request)).await,
+            None => self.client.execute(request).await,
+        }
+        .context("Failed to send GraphQL request")?;
+        let ttfb = started.elapsed();
+        let connection = self.connection_timer.as_ref().map(ConnectionTimer::take).unwrap_or_default();
+        let response_head_size = timing::response_head_size(&response);
 
         let status = response.status();
-        let body_text = response
-            .text()
+        let headers = Headers::from(response.headers());
+        let body_bytes = response
+            .bytes()
             .await
             .context("Failed to read GraphQL response")?;
+        let total = started.elapsed();
+        let body_text = String::from_utf8_lossy(&body_bytes).into_owned();
+        let timings = Timings {
+            dns: connection.dns,
+            connect: connection.connect,
+            tls: connection.tls,
+            ttfb,
+            download: total - ttfb,
+            total,
+            request_size,
+            response_size: response_head_size + body_bytes.len() as u64,
+        };
 
         if !status.is_success() {
             return Err(anyhow::anyhow!(
@@ -85,15 +134,23 @@ impl GraphQLClient {
             ));
         }
 
-        Ok(body_text)
+        Ok(GraphQLResponse {
+            request: sent,
+            status: status.as_u16(),
+            headers,
+            body: body_text,
+            timings,
+        })
     }
 
-    pub fn print_response(&self, response: &str) {
+    pub fn print_response(&self, response: &GraphQLResponse) {
+        println!("Timing: {}", response.timings);
         // Try to pretty-print JSON
-        if let Ok(json) = serde_json::from_str::<serde_json::Value>(response) {
-            println!("{}", serde_json::to_string_pretty(&json).unwrap_or(response.to_string()));
+        let body = &response.body;
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+            println!("{}", serde_json::to_string_pretty(&json).unwrap_or(body.to_string()));
         } else {
-            println!("{}", response);
+            println!("{}", body);
         }
     }
 }
@@ -101,16 +158,16 @@ impl GraphQLClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::HttpClientConfig;
     use crate::env::EnvironmentManager;
-    use std::collections::HashMap;
+    use crate::parser::Span;
 
     #[test]
     fn test_graphql_client_creation() {
         let client = Client::new();
         let env_manager = EnvironmentManager::new(".");
-        let gql_client = GraphQLClient::new(client, env_manager);
         // Just test that it can be created
-        assert!(true);
+        let _gql_client = GraphQLClient::new(client, env_manager);
     }
 
     #[test]
@@ -144,8 +201,7 @@ mod tests {
         
         let json_response = r#"{"data":{"users":[{"id":"1"}]}}"#;
         // Just test that it doesn't panic
-        gql_client.print_response(json_response);
-        assert!(true);
+        gql_client.print_response(&GraphQLResponse { request: Default::default(), status: 200, headers: Default::default(), body: json_response.to_string(), timings: Timings::default() });
     }
 
     #[test]
@@ -156,7 +212,47 @@ mod tests {
         
         let plain_response = "Not JSON";
         // Just test that it doesn't panic
-        gql_client.print_response(plain_response);
-        assert!(true);
+        gql_client.print_response(&GraphQLResponse { request: Default::default(), status: 200, headers: Default::default(), body: plain_response.to_string(), timings: Timings::default() });
+    }
+
+    #[tokio::test]
+    async fn test_execute_request_records_timings() {
+        use hyper::service::{make_service_fn, service_fn};
+
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(service_fn(|_req: hyper::Request<hyper::Body>| async {
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(hyper::Body::from(r#"{"data":{"ok":true}}"#)))
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let uri = format!("http://localhost:{}/graphql", server.local_addr().port());
+        tokio::spawn(server);
+
+        let connection_timer = ConnectionTimer::new();
+        let client = HttpClientConfig::new().with_connection_timer(connection_timer.clone()).build_client(".").unwrap();
+        let gql_client = GraphQLClient::new(client, EnvironmentManager::new(".")).with_connection_timer(connection_timer);
+        let request = GraphQLRequest {
+            name: None,
+            tags: Vec::new(),
+            comments: Vec::new(),
+            span: Span::default(),
+            uri,
+            query: "query { ok }".to_string(),
+            query_comments: Vec::new(),
+            variables: None,
+            headers: Default::default(),
+            header_comments: Vec::new(),
+        };
+
+        let response = gql_client.execute_request(&request, None).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, r#"{"data":{"ok":true}}"#);
+        assert!(response.timings.dns.is_some());
+        assert!(response.timings.request_size > "query { ok }".len() as u64);
+        assert!(response.timings.response_size > response.body.len() as u64);
+        assert!(response.timings.ttfb <= response.timings.total);
+
+        let response = gql_client.execute_request(&request, None).await.unwrap();
+        assert_eq!(response.timings.dns, None);
     }
 }
@@ -0,0 +1,222 @@
+//! Ordered, multi-valued HTTP headers with case-insensitive names.
+
+use std::slice;
+
+/// Header fields in the order they were written or received. Names keep their
+/// original spelling but are compared case-insensitively, and a name may occur
+/// more than once, e.g. for `Set-Cookie` or `Accept`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    fields: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a field after the existing ones, keeping fields with the same name.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.fields.push((name.into(), value.into()));
+    }
+
+    /// Sets the value of `name`, replacing all of its fields. The first of them
+    /// keeps its position; a new name is added at the end.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        match self.fields.iter().position(|(k, _)| k.eq_ignore_ascii_case(&name)) {
+            Some(index) => {
+                let mut position = 0;
+                self.fields.retain(|(k, _)| {
+                    position += 1;
+                    position - 1 <= index || !k.eq_ignore_ascii_case(&name)
+                });
+                self.fields[index] = (name, value.into());
+            }
+            None => self.fields.push((name, value.into())),
+        }
+    }
+
+    /// The first value of `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// All values of `name`, in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Removes all fields named `name` and returns their values.
+    pub fn remove(&mut self, name: &str) -> Vec<String> {
+        let (removed, kept) = std::mem::take(&mut self.fields)
+            .into_iter()
+            .partition(|(k, _)| k.eq_ignore_ascii_case(name));
+        self.fields = kept;
+        removed.into_iter().map(|(_, v)| v).collect()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Number of fields, counting each value of a repeated name.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Rewrites names with `canonical_name`, keeping the order of the fields.
+    pub fn canonicalize_names(&mut self) {
+        for (name, _) in &mut self.fields {
+            *name = canonical_name(name);
+        }
+    }
+}
+
+/// `content-type` and `CONTENT-TYPE` become `Content-Type`. Names already
+/// written in mixed case, such as `X-Request-ID` or `ETag`, are kept.
+pub fn canonical_name(name: &str) -> String {
+    let has_lower = name.chars().any(|c| c.is_ascii_lowercase());
+    let has_upper = name.chars().any(|c| c.is_ascii_uppercase());
+    if has_lower && has_upper {
+        return name.to_string();
+    }
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+pub type Iter<'a> = std::iter::Map<slice::Iter<'a, (String, String)>, fn(&'a (String, String)) -> (&'a str, &'a str)>;
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut headers = Headers::new();
+        headers.extend(iter);
+        headers
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> Extend<(K, V)> for Headers {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.append(name, value);
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<String>, const N: usize> From<[(K, V); N]> for Headers {
+    fn from(fields: [(K, V); N]) -> Self {
+        fields.into_iter().collect()
+    }
+}
+
+/// Received headers, one field per value. Values that are not valid UTF-8 are
+/// converted lossily.
+impl From<&reqwest::header::HeaderMap> for Headers {
+    fn from(map: &reqwest::header::HeaderMap) -> Self {
+        map.iter()
+            .map(|(k, v)| (k.as_str(), String::from_utf8_lossy(v.as_bytes()).into_owned()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_insensitive_lookup_keeps_spelling() {
+        let headers = Headers::from([("Content-Type", "application/json"), ("X-Request-ID", "42")]);
+        assert_eq!(headers.get("content-type"), Some("application/json"));
+        assert_eq!(headers.get("x-request-id"), Some("42"));
+        assert!(headers.contains_key("CONTENT-TYPE"));
+        assert!(!headers.contains_key("Accept"));
+        let names: Vec<&str> = headers.iter().map(|(k, _)| k).collect();
+        assert_eq!(names, ["Content-Type", "X-Request-ID"]);
+    }
+
+    #[test]
+    fn test_repeated_fields_keep_order() {
+        let mut headers = Headers::new();
+        headers.append("Accept", "text/html");
+        headers.append("Set-Cookie", "a=1");
+        headers.append("accept", "application/json");
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers.get("Accept"), Some("text/html"));
+        assert_eq!(headers.get_all("ACCEPT").collect::<Vec<_>>(), ["text/html", "application/json"]);
+        assert_eq!(
+            headers.iter().collect::<Vec<_>>(),
+            [("Accept", "text/html"), ("Set-Cookie", "a=1"), ("accept", "application/json")]
+        );
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut headers = Headers::from([("Accept", "text/html"), ("Host", "example.com"), ("accept", "text/xml")]);
+        headers.insert("ACCEPT", "*/*");
+        assert_eq!(headers.iter().collect::<Vec<_>>(), [("ACCEPT", "*/*"), ("Host", "example.com")]);
+        headers.insert("User-Agent", "test");
+        assert_eq!(headers.iter().next_back(), Some(("User-Agent", "test")));
+
+        assert_eq!(headers.remove("host"), ["example.com"]);
+        assert_eq!(headers.remove("host"), Vec::<String>::new());
+        assert_eq!(headers.len(), 2);
+    }
+
+    #[test]
+    fn test_canonical_names() {
+        assert_eq!(canonical_name("content-type"), "Content-Type");
+        assert_eq!(canonical_name("ACCEPT"), "Accept");
+        assert_eq!(canonical_name("x-api-key"), "X-Api-Key");
+        assert_eq!(canonical_name("X-Request-ID"), "X-Request-ID");
+        assert_eq!(canonical_name("ETag"), "ETag");
+
+        let mut headers = Headers::from([("accept", "*/*"), ("X-Request-ID", "1"), ("ACCEPT", "text/html")]);
+        headers.canonicalize_names();
+        assert_eq!(
+            headers.iter().collect::<Vec<_>>(),
+            [("Accept", "*/*"), ("X-Request-ID", "1"), ("Accept", "text/html")]
+        );
+    }
+
+    #[test]
+    fn test_from_header_map() {
+        let mut map = reqwest::header::HeaderMap::new();
+        map.append("set-cookie", "a=1".parse().unwrap());
+        map.append("set-cookie", "b=2".parse().unwrap());
+        map.insert("content-type", "text/plain".parse().unwrap());
+        let headers = Headers::from(&map);
+        assert_eq!(headers.get_all("Set-Cookie").collect::<Vec<_>>(), ["a=1", "b=2"]);
+        assert_eq!(headers.get("Content-Type"), Some("text/plain"));
+    }
+}
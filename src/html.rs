@@ -0,0 +1,310 @@
+//! A single static HTML page with the requests of a run, for browsing
+//! without a CI server.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::headers::Headers;
+use crate::summary::{Outcome, RequestResult, RunSummary};
+use crate::timing::{format_duration, Timings};
+
+const STYLE: &str = "\
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; margin: 2rem; color: #1f2328; }
+h1 { font-size: 1.5rem; }
+h2 { font-size: 1.2rem; margin: 2rem 0 0.25rem; }
+h3 { font-size: 0.9rem; margin: 1rem 0 0.25rem; text-transform: uppercase; color: #59636e; }
+.meta { color: #59636e; font-size: 0.9rem; }
+details { border: 1px solid #d1d9e0; border-radius: 6px; margin: 0.5rem 0; }
+summary { cursor: pointer; padding: 0.5rem 0.75rem; }
+details > div { padding: 0 0.75rem 0.75rem; border-top: 1px solid #d1d9e0; }
+.badge { display: inline-block; min-width: 4rem; text-align: center; border-radius: 4px; padding: 0 0.4rem; color: #fff; font-size: 0.8rem; }
+.passed .badge { background: #1a7f37; }
+.failed .badge { background: #cf222e; }
+.error .badge { background: #9a6700; }
+pre { background: #f6f8fa; padding: 0.5rem; overflow-x: auto; white-space: pre-wrap; word-break: break-all; margin: 0.25rem 0; }
+table.waterfall { border-collapse: collapse; width: 100%; font-size: 0.85rem; }
+table.waterfall td { padding: 0.1rem 0.5rem 0.1rem 0; white-space: nowrap; }
+table.waterfall td.track { width: 100%; }
+.bar { height: 0.8rem; background: #0969da; min-width: 1px; }
+ul.checks { list-style: none; padding: 0; margin: 0; }
+ul.checks .pass { color: #1a7f37; }
+ul.checks .fail { color: #cf222e; }
+";
+
+/// One section per file with an entry per request that ran, showing the
+/// request and response, the timing waterfall and the checks. Failed
+/// requests start expanded.
+pub fn render(summaries: &[RunSummary], generated: DateTime<Utc>) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>rest-client report</title>\n");
+    let _ = write!(html, "<style>\n{}</style>\n</head>\n<body>\n", STYLE);
+    html.push_str("<h1>rest-client report</h1>\n");
+    let _ = writeln!(
+        html,
+        "<p class=\"meta\">Generated {} for {} file(s)</p>",
+        generated.to_rfc3339_opts(SecondsFormat::Secs, true),
+        summaries.len(),
+    );
+    for summary in summaries {
+        let _ = writeln!(html, "<section>\n<h2>{}</h2>", escape(&summary.file.display().to_string()));
+        let _ = writeln!(
+            html,
+            "<p class=\"meta\">Environment: {} &middot; {}</p>",
+            escape(summary.environment.as_deref().unwrap_or("none")),
+            summary.totals(),
+        );
+        if let Some(error) = &summary.error {
+            let _ = writeln!(html, "<h3>Error</h3>\n<pre>{}</pre>", escape(error));
+        }
+        for result in summary.results.iter().filter(|result| result.outcome != Outcome::Skipped) {
+            write_entry(&mut html, result);
+        }
+        html.push_str("</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn write_entry(html: &mut String, result: &RequestResult) {
+    let (class, open) = match &result.outcome {
+        Outcome::Passed => ("passed", ""),
+        Outcome::Failed(_) => ("failed", " open"),
+        Outcome::Error(_) => ("error", " open"),
+        Outcome::Skipped => return,
+    };
+    let mut meta = vec![result.protocol.to_string()];
+    meta.extend(result.status.map(|status| status.to_string()));
+    meta.extend(result.duration.map(format_duration));
+    let _ = writeln!(
+        html,
+        "<details class=\"{}\"{}>\n<summary><span class=\"badge\">{}</span> {} <span class=\"meta\">{}</span></summary>\n<div>",
+        class,
+        open,
+        class,
+        escape(&result.name),
+        escape(&meta.join(" · ")),
+    );
+
+    if result.no_log {
+        html.push_str("<p class=\"meta\">Request and response not logged (@no-log)</p>\n");
+    }
+    if let Some(request) = &result.request {
+        html.push_str("<h3>Request</h3>\n");
+        let _ = writeln!(
+            html,
+            "<pre>{} {}\n{}</pre>",
+            escape(&request.method),
+            escape(&request.url),
+            escape(&header_lines(&request.headers)),
+        );
+        if let Some(body) = request.body.as_deref().filter(|body| !body.is_empty()) {
+            let _ = writeln!(html, "<pre>{}</pre>", escape(&pretty(body)));
+        }
+    }
+    if let Some(response) = &result.response {
+        html.push_str("<h3>Response</h3>\n");
+        let status = result.status.map(|status| format!("Status: {}\n", status)).unwrap_or_default();
+        let _ = writeln!(html, "<pre>{}{}</pre>", status, escape(&header_lines(&response.headers)));
+        if !response.body.is_empty() {
+            let _ = writeln!(html, "<pre>{}</pre>", escape(&pretty(&response.body)));
+        }
+    }
+    if let Some(timings) = &result.timings {
+        html.push_str("<h3>Timing</h3>\n");
+        write_waterfall(html, timings);
+    }
+    if !result.tests.is_empty() {
+        html.push_str("<h3>Checks</h3>\n<ul class=\"checks\">\n");
+        for test in &result.tests {
+            let (class, mark) = if test.passed { ("pass", "✔") } else { ("fail", "✘") };
+            let message = test.message.as_deref().map(|message| format!(": {}", message)).unwrap_or_default();
+            let _ = writeln!(html, "<li class=\"{}\">{} {}{}</li>", class, mark, escape(&test.name), escape(&message));
+        }
+        html.push_str("</ul>\n");
+    }
+    if let Outcome::Error(error) = &result.outcome {
+        let _ = writeln!(html, "<h3>Error</h3>\n<pre>{}</pre>", escape(error));
+    }
+    html.push_str("</div>\n</details>\n");
+}
+
+/// A row per phase that happened, with a bar placed after the previous phases.
+/// Opening the connection is split off the wait for the response headers.
+fn write_waterfall(html: &mut String, timings: &Timings) {
+    let phases: Vec<(&str, Duration)> = [
+        ("DNS", timings.dns),
+        ("Connect", timings.connect),
+        ("TLS", timings.tls),
+        ("Waiting", Some(timings.waiting())),
+        ("Download", Some(timings.download)),
+    ]
+    .into_iter()
+    .filter_map(|(name, duration)| Some((name, duration?)))
+    .collect();
+    let scale = phases.iter().map(|(_, duration)| duration.as_secs_f64()).sum::<f64>().max(f64::EPSILON);
+
+    html.push_str("<table class=\"waterfall\">\n");
+    let mut start = 0.0;
+    for (name, duration) in phases {
+        let width = duration.as_secs_f64() / scale * 100.0;
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"track\"><div class=\"bar\" style=\"margin-left: {:.1}%; width: {:.1}%\"></div></td><td>{}</td></tr>",
+            name,
+            start,
+            width,
+            format_duration(duration),
+        );
+        start += width;
+    }
+    let _ = writeln!(html, "<tr><td>Total</td><td class=\"track\"></td><td>{}</td></tr>", format_duration(timings.total));
+    html.push_str("</table>\n");
+}
+
+fn header_lines(headers: &Headers) -> String {
+    headers.iter().map(|(name, value)| format!("{}: {}\n", name, value)).collect()
+}
+
+/// Indents JSON bodies; other bodies are shown as they are.
+fn pretty(body: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(value @ (serde_json::Value::Object(_) | serde_json::Value::Array(_))) => {
+            serde_json::to_string_pretty(&value).unwrap_or_else(|_| body.to_string())
+        }
+        _ => body.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SentRequest;
+    use crate::parser::HttpFileParser;
+    use crate::script::TestResult;
+    use crate::summary::ReceivedResponse;
+    use chrono::TimeZone;
+
+    fn summary() -> RunSummary {
+        let content = r###"### Login
+POST https://api.example.com/login
+### List <users>
+GET https://api.example.com/users
+### Events
+WEBSOCKET ws://localhost:8080/events
+###
+GET https://api.example.com/health
+"###;
+        let requests = HttpFileParser::new(content.to_string()).parse().unwrap();
+        let mut summary = RunSummary::new("api.http");
+        summary.environment = Some("dev".to_string());
+        let mut login = RequestResult::new(&requests[0], Outcome::Passed).with_status(200);
+        login.request = Some(SentRequest {
+            method: "POST".to_string(),
+            url: "https://api.example.com/login".to_string(),
+            headers: Headers::from([("Content-Type", "application/json")]),
+            body: Some("{\"user\": \"admin\"}".to_string()),
+        });
+        login.response = Some(ReceivedResponse {
+            headers: Headers::from([("Content-Type", "application/json")]),
+            body: "{\"token\":\"abc\",\"roles\":[\"admin\"]}".to_string(),
+        });
+        login.timings = Some(Timings {
+            dns: Some(Duration::from_millis(10)),
+            connect: Some(Duration::from_millis(5)),
+            tls: None,
+            ttfb: Duration::from_millis(30),
+            download: Duration::from_millis(20),
+            total: Duration::from_millis(50),
+            request_size: 0,
+            response_size: 0,
+        });
+        login.tests = vec![TestResult {
+            name: "token is set".to_string(),
+            passed: true,
+            message: None,
+        }];
+        summary.push(login);
+        let failed = TestResult {
+            name: "status == 200".to_string(),
+            passed: false,
+            message: Some("status was 500".to_string()),
+        };
+        let mut users = RequestResult::new(&requests[1], Outcome::Failed(vec![failed.clone()])).with_status(500);
+        users.tests = vec![failed];
+        summary.push(users);
+        summary.push(RequestResult::new(&requests[2], Outcome::Error("Failed to connect".to_string())));
+        summary.push(RequestResult::new(&requests[3], Outcome::Skipped));
+        summary
+    }
+
+    fn generated() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let html = render(&[summary()], generated());
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.ends_with("</body>\n</html>\n"));
+        assert!(html.contains("<p class=\"meta\">Generated 2024-05-01T12:00:00Z for 1 file(s)</p>"));
+        assert!(html.contains("<h2>api.http</h2>\n<p class=\"meta\">Environment: dev &middot; 4 request(s): 1 passed, 1 failed, 1 error(s), 1 not run</p>"));
+        assert!(html.contains("<details class=\"passed\">\n<summary><span class=\"badge\">passed</span> Login <span class=\"meta\">HTTP · 200</span></summary>"));
+        assert!(html.contains("<pre>POST https://api.example.com/login\nContent-Type: application/json\n</pre>"));
+        assert!(html.contains("<pre>Status: 200\nContent-Type: application/json\n</pre>"));
+        assert!(html.contains("<pre>{\n  &quot;roles&quot;: [\n    &quot;admin&quot;\n  ],\n  &quot;token&quot;: &quot;abc&quot;\n}</pre>"));
+        assert!(html.contains("<li class=\"pass\">✔ token is set</li>"));
+        assert!(html.contains("<details class=\"failed\" open>\n<summary><span class=\"badge\">failed</span> List &lt;users&gt;"));
+        assert!(html.contains("<li class=\"fail\">✘ status == 200: status was 500</li>"));
+        assert!(html.contains("<h3>Error</h3>\n<pre>Failed to connect</pre>"));
+        // Only requests that ran are listed
+        assert!(!html.contains("health"));
+        // Nothing is loaded from elsewhere
+        assert!(!html.contains("<script") && !html.contains("<link"));
+    }
+
+    #[test]
+    fn test_waterfall() {
+        let html = render(&[summary()], generated());
+        assert!(html.contains("<tr><td>DNS</td><td class=\"track\"><div class=\"bar\" style=\"margin-left: 0.0%; width: 20.0%\"></div></td><td>10 ms</td></tr>"));
+        assert!(html.contains("<tr><td>Connect</td><td class=\"track\"><div class=\"bar\" style=\"margin-left: 20.0%; width: 10.0%\"></div></td><td>5.0 ms</td></tr>"));
+        assert!(html.contains("<tr><td>Waiting</td><td class=\"track\"><div class=\"bar\" style=\"margin-left: 30.0%; width: 30.0%\"></div></td><td>15 ms</td></tr>"));
+        assert!(html.contains("<tr><td>Download</td><td class=\"track\"><div class=\"bar\" style=\"margin-left: 60.0%; width: 40.0%\"></div></td><td>20 ms</td></tr>"));
+        assert!(html.contains("<tr><td>Total</td><td class=\"track\"></td><td>50 ms</td></tr>"));
+        assert!(!html.contains("<td>TLS</td>"));
+    }
+
+    #[test]
+    fn test_no_log() {
+        let requests = HttpFileParser::new("### Login\n# @no-log\nPOST https://api.example.com/login\n".to_string())
+            .parse()
+            .unwrap();
+        let mut summary = RunSummary::new("api.http");
+        summary.push(RequestResult::new(&requests[0], Outcome::Passed).with_status(200));
+        let html = render(&[summary], generated());
+        assert!(html.contains("<p class=\"meta\">Request and response not logged (@no-log)</p>"));
+        assert!(!html.contains("<h3>Request</h3>") && !html.contains("<h3>Response</h3>"));
+    }
+
+    #[test]
+    fn test_pretty() {
+        assert_eq!(pretty("[1,2]"), "[\n  1,\n  2\n]");
+        assert_eq!(pretty("plain <text>"), "plain <text>");
+        assert_eq!(pretty("42"), "42");
+    }
+}
@@ -0,0 +1,204 @@
+//! Minimal JSONPath evaluator used by assertions and response references.
+//! Supports `$`, `.key`, `['key']`, `[n]` (negative from the end), `[*]`,
+//! `.*`, recursive descent `..key` and a trailing `.length()`.
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+    Descendant(String),
+    Length,
+}
+
+/// Evaluates `path` against `root`, returning every matched value.
+pub fn select(root: &Value, path: &str) -> Result<Vec<Value>> {
+    let segments = parse(path)?;
+    let mut current = vec![root.clone()];
+
+    for segment in &segments {
+        let mut next = Vec::new();
+        for value in &current {
+            match segment {
+                Segment::Key(key) => {
+                    if let Some(v) = value.get(key) {
+                        next.push(v.clone());
+                    }
+                }
+                Segment::Index(index) => {
+                    if let Value::Array(items) = value {
+                        let idx = if *index < 0 { items.len() as i64 + index } else { *index };
+                        if let Some(v) = usize::try_from(idx).ok().and_then(|i| items.get(i)) {
+                            next.push(v.clone());
+                        }
+                    }
+                }
+                Segment::Wildcard => match value {
+                    Value::Array(items) => next.extend(items.iter().cloned()),
+                    Value::Object(map) => next.extend(map.values().cloned()),
+                    _ => {}
+                },
+                Segment::Descendant(key) => collect_descendants(value, key, &mut next),
+                Segment::Length => match value {
+                    Value::Array(items) => next.push(Value::from(items.len())),
+                    Value::Object(map) => next.push(Value::from(map.len())),
+                    Value::String(s) => next.push(Value::from(s.chars().count())),
+                    _ => {}
+                },
+            }
+        }
+        current = next;
+    }
+
+    Ok(current)
+}
+
+/// Evaluates `path` and collapses the result: a single match is returned as
+/// is, several matches as an array, and no match as `None`.
+pub fn select_one(root: &Value, path: &str) -> Result<Option<Value>> {
+    let mut values = select(root, path)?;
+    Ok(match values.len() {
+        0 => None,
+        1 => values.pop(),
+        _ => Some(Value::Array(values)),
+    })
+}
+
+fn collect_descendants(value: &Value, key: &str, out: &mut Vec<Value>) {
+    match value {
+        Value::Object(map) => {
+            if let Some(v) = map.get(key) {
+                out.push(v.clone());
+            }
+            for v in map.values() {
+                collect_descendants(v, key, out);
+            }
+        }
+        Value::Array(items) => {
+            for v in items {
+                collect_descendants(v, key, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>> {
+    let path = path.trim();
+    let Some(mut rest) = path.strip_prefix('$') else {
+        bail!("JSONPath must start with '$': {}", path);
+    };
+
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("..") {
+            let (name, tail) = split_name(after);
+            if name.is_empty() {
+                bail!("Expected a key after '..' in JSONPath: {}", path);
+            }
+            segments.push(Segment::Descendant(name.to_string()));
+            rest = tail;
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let (name, tail) = split_name(after);
+            match name {
+                "" => bail!("Expected a key after '.' in JSONPath: {}", path),
+                "*" => segments.push(Segment::Wildcard),
+                "length" if tail.starts_with("()") => {
+                    segments.push(Segment::Length);
+                    rest = &tail[2..];
+                    continue;
+                }
+                _ => segments.push(Segment::Key(name.to_string())),
+            }
+            rest = tail;
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let Some(end) = after.find(']') else {
+                bail!("Unclosed '[' in JSONPath: {}", path);
+            };
+            let inner = after[..end].trim();
+            if inner == "*" {
+                segments.push(Segment::Wildcard);
+            } else if let Some(key) = inner
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+            {
+                segments.push(Segment::Key(key.to_string()));
+            } else if let Ok(index) = inner.parse::<i64>() {
+                segments.push(Segment::Index(index));
+            } else {
+                bail!("Unsupported JSONPath selector [{}] in: {}", inner, path);
+            }
+            rest = &after[end + 1..];
+        } else {
+            bail!("Unexpected '{}' in JSONPath: {}", rest, path);
+        }
+    }
+
+    Ok(segments)
+}
+
+fn split_name(s: &str) -> (&str, &str) {
+    let end = s.find(['.', '[', '(']).unwrap_or(s.len());
+    (&s[..end], &s[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> Value {
+        json!({
+            "token": "abc",
+            "items": [
+                { "id": 1, "tags": ["a", "b"] },
+                { "id": 2, "tags": [] }
+            ],
+            "meta": { "total": 2, "next-page": null }
+        })
+    }
+
+    #[test]
+    fn test_select_key_and_index() {
+        assert_eq!(select_one(&sample(), "$.token").unwrap(), Some(json!("abc")));
+        assert_eq!(select_one(&sample(), "$.items[1].id").unwrap(), Some(json!(2)));
+        assert_eq!(select_one(&sample(), "$.items[-1].id").unwrap(), Some(json!(2)));
+        assert_eq!(select_one(&sample(), "$['meta']['next-page']").unwrap(), Some(Value::Null));
+    }
+
+    #[test]
+    fn test_select_root() {
+        assert_eq!(select_one(&json!([1, 2]), "$").unwrap(), Some(json!([1, 2])));
+    }
+
+    #[test]
+    fn test_select_wildcard_and_descendants() {
+        assert_eq!(select_one(&sample(), "$.items[*].id").unwrap(), Some(json!([1, 2])));
+        assert_eq!(select(&sample(), "$..id").unwrap(), vec![json!(1), json!(2)]);
+        assert_eq!(select(&sample(), "$.meta.*").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_select_length() {
+        assert_eq!(select_one(&sample(), "$.items.length()").unwrap(), Some(json!(2)));
+        assert_eq!(select_one(&sample(), "$.items[0].tags.length()").unwrap(), Some(json!(2)));
+        assert_eq!(select_one(&sample(), "$.token.length()").unwrap(), Some(json!(3)));
+    }
+
+    #[test]
+    fn test_select_missing() {
+        assert_eq!(select_one(&sample(), "$.missing.key").unwrap(), None);
+        assert_eq!(select_one(&sample(), "$.items[5]").unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        assert!(select(&sample(), "items").is_err());
+        assert!(select(&sample(), "$.items[").is_err());
+        assert!(select(&sample(), "$.items[?(@.id)]").is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! JUnit XML reports, as read by CI servers such as GitLab and Jenkins.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::summary::{Outcome, RequestResult, RunSummary};
+use crate::timing::format_duration;
+
+/// One `<testsuite>` per file and one `<testcase>` per request. Failed checks
+/// are `<failure>`s, requests without a response `<error>`s and requests that
+/// did not run `<skipped>`. A file that could not be run has a single
+/// `<testcase>` named after it with an `<error>`.
+pub fn render(summaries: &[RunSummary]) -> String {
+    let total = |count: fn(&RunSummary) -> usize| summaries.iter().map(count).sum::<usize>();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"rest-client\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">",
+        total(testcases),
+        total(RunSummary::failed),
+        total(RunSummary::errors),
+        total(RunSummary::skipped),
+        seconds(summaries.iter().map(RunSummary::duration).sum()),
+    );
+    for summary in summaries {
+        let file = escape(&summary.file.display().to_string());
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">",
+            file,
+            testcases(summary),
+            summary.failed(),
+            summary.errors(),
+            summary.skipped(),
+            seconds(summary.duration()),
+        );
+        if let Some(error) = &summary.error {
+            let _ = writeln!(xml, "    <testcase name=\"{}\" classname=\"{}\" time=\"0.000\">", file, file);
+            let _ = writeln!(
+                xml,
+                "      <error message=\"{}\" type=\"FileError\">{}</error>",
+                escape(error.lines().next().unwrap_or_default()),
+                escape(error),
+            );
+            xml.push_str("    </testcase>\n");
+        }
+        for result in &summary.results {
+            write_testcase(&mut xml, &file, result);
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn testcases(summary: &RunSummary) -> usize {
+    summary.results.len() + usize::from(summary.error.is_some())
+}
+
+fn write_testcase(xml: &mut String, classname: &str, result: &RequestResult) {
+    let _ = writeln!(
+        xml,
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\">",
+        escape(&result.name),
+        classname,
+        seconds(result.duration.unwrap_or_default()),
+    );
+    match &result.outcome {
+        Outcome::Passed => {}
+        Outcome::Failed(tests) => {
+            let details: Vec<String> = tests
+                .iter()
+                .map(|test| match &test.message {
+                    Some(message) => format!("{}: {}", test.name, message),
+                    None => test.name.clone(),
+                })
+                .collect();
+            let _ = writeln!(
+                xml,
+                "      <failure message=\"{} check(s) failed\" type=\"AssertionError\">{}</failure>",
+                tests.len(),
+                escape(&details.join("\n")),
+            );
+        }
+        Outcome::Error(error) => {
+            let message = error.lines().next().unwrap_or_default();
+            let _ = writeln!(
+                xml,
+                "      <error message=\"{}\" type=\"RequestError\">{}</error>",
+                escape(message),
+                escape(error),
+            );
+        }
+        Outcome::Skipped => {
+            xml.push_str("      <skipped message=\"Not run because an earlier request failed\"/>\n");
+        }
+    }
+    if let Some(output) = system_out(result) {
+        let _ = writeln!(xml, "      <system-out>{}</system-out>", escape(&output));
+    }
+    xml.push_str("    </testcase>\n");
+}
+
+/// The request and response as sent and received, with the timings.
+fn system_out(result: &RequestResult) -> Option<String> {
+    let mut out = String::new();
+    if result.no_log {
+        out.push_str("Request and response not logged (@no-log)\n\n");
+    }
+    if let Some(request) = &result.request {
+        let _ = writeln!(out, "{} {}", request.method, request.url);
+        for (name, value) in &request.headers {
+            let _ = writeln!(out, "{}: {}", name, value);
+        }
+        if let Some(body) = &request.body {
+            let _ = writeln!(out, "\n{}", body);
+        }
+        out.push('\n');
+    }
+    if let Some(response) = &result.response {
+        if let Some(status) = result.status {
+            let _ = writeln!(out, "Status: {}", status);
+        }
+        for (name, value) in &response.headers {
+            let _ = writeln!(out, "{}: {}", name, value);
+        }
+        if !response.body.is_empty() {
+            let _ = writeln!(out, "\n{}", response.body);
+        }
+        out.push('\n');
+    }
+    match (&result.timings, result.duration) {
+        (Some(timings), _) => {
+            let _ = writeln!(out, "Timing: {}", timings);
+        }
+        (None, Some(duration)) => {
+            let _ = writeln!(out, "Duration: {}", format_duration(duration));
+        }
+        (None, None) => {}
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+/// Escapes text for an attribute or element, dropping characters XML 1.0
+/// doesn't allow, such as those in binary bodies.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SentRequest;
+    use crate::headers::Headers;
+    use crate::parser::HttpFileParser;
+    use crate::script::TestResult;
+    use crate::summary::ReceivedResponse;
+
+    fn summary() -> RunSummary {
+        let content = r###"### Login
+POST https://api.example.com/login
+### List <users>
+GET https://api.example.com/users
+### Events
+WEBSOCKET ws://localhost:8080/events
+###
+GET https://api.example.com/health
+"###;
+        let requests = HttpFileParser::new(content.to_string()).parse().unwrap();
+        let mut summary = RunSummary::new("api.http");
+        let mut login = RequestResult::new(&requests[0], Outcome::Passed)
+            .with_status(200)
+            .with_duration(Duration::from_millis(35));
+        login.request = Some(SentRequest {
+            method: "POST".to_string(),
+            url: "https://api.example.com/login".to_string(),
+            headers: Headers::from([("Content-Type", "application/json")]),
+            body: Some("{\"user\": \"admin\"}".to_string()),
+        });
+        login.response = Some(ReceivedResponse {
+            headers: Headers::from([("Content-Type", "application/json")]),
+            body: "{\"token\": \"abc\"}".to_string(),
+        });
+        summary.push(login);
+        summary.push(
+            RequestResult::new(
+                &requests[1],
+                Outcome::Failed(vec![TestResult {
+                    name: "status == 200".to_string(),
+                    passed: false,
+                    message: Some("status was 500".to_string()),
+                }]),
+            )
+            .with_status(500)
+            .with_duration(Duration::from_millis(120)),
+        );
+        summary.push(
+            RequestResult::new(&requests[2], Outcome::Error("Failed to connect\n\nCaused by: refused".to_string()))
+                .with_duration(Duration::from_millis(5)),
+        );
+        summary.push(RequestResult::new(&requests[3], Outcome::Skipped));
+        summary
+    }
+
+    #[test]
+    fn test_render() {
+        let xml = render(&[summary()]);
+        assert!(xml.starts_with(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"rest-client\" tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\" time=\"0.160\">\n  <testsuite name=\"api.http\" tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\" time=\"0.160\">\n"
+        ));
+        assert!(xml.contains(
+            "    <testcase name=\"Login\" classname=\"api.http\" time=\"0.035\">\n      <system-out>POST https://api.example.com/login\nContent-Type: application/json\n\n{&quot;user&quot;: &quot;admin&quot;}\n\nStatus: 200\nContent-Type: application/json\n\n{&quot;token&quot;: &quot;abc&quot;}\n\nDuration: 35 ms\n</system-out>\n    </testcase>\n"
+        ));
+        assert!(xml.contains(
+            "    <testcase name=\"List &lt;users&gt;\" classname=\"api.http\" time=\"0.120\">\n      <failure message=\"1 check(s) failed\" type=\"AssertionError\">status == 200: status was 500</failure>\n"
+        ));
+        assert!(xml.contains(
+            "      <error message=\"Failed to connect\" type=\"RequestError\">Failed to connect\n\nCaused by: refused</error>\n"
+        ));
+        assert!(xml.contains(
+            "    <testcase name=\"GET https://api.example.com/health\" classname=\"api.http\" time=\"0.000\">\n      <skipped message=\"Not run because an earlier request failed\"/>\n    </testcase>\n"
+        ));
+        assert!(xml.ends_with("  </testsuite>\n</testsuites>\n"));
+    }
+
+    #[test]
+    fn test_one_testsuite_per_file() {
+        let mut other = RunSummary::new("other.http");
+        other.results = summary().results[..1].to_vec();
+        let xml = render(&[summary(), other]);
+        assert!(xml.contains("<testsuites name=\"rest-client\" tests=\"5\" failures=\"1\" errors=\"1\" skipped=\"1\" time=\"0.195\">"));
+        assert!(xml.contains("  <testsuite name=\"other.http\" tests=\"1\" failures=\"0\" errors=\"0\" skipped=\"0\" time=\"0.035\">"));
+        assert_eq!(xml.matches("<testsuite ").count(), 2);
+    }
+
+    #[test]
+    fn test_no_log_and_file_errors() {
+        let requests = HttpFileParser::new("###\n# @no-log\nPOST https://api.example.com/login\n".to_string())
+            .parse()
+            .unwrap();
+        let mut summary = RunSummary::new("secret.http");
+        summary.push(RequestResult::new(&requests[0], Outcome::Passed).with_duration(Duration::from_millis(5)));
+        let mut broken = RunSummary::new("broken.http");
+        broken.error = Some("Failed to parse file: \"broken.http\"\n\nCaused by: bad".to_string());
+
+        let xml = render(&[summary, broken]);
+        assert!(xml.contains("<system-out>Request and response not logged (@no-log)\n\nDuration: 5.0 ms\n</system-out>"));
+        assert!(xml.contains("<testsuites name=\"rest-client\" tests=\"2\" failures=\"0\" errors=\"1\" skipped=\"0\" time=\"0.005\">"));
+        assert!(xml.contains(
+            "  <testsuite name=\"broken.http\" tests=\"1\" failures=\"0\" errors=\"1\" skipped=\"0\" time=\"0.000\">\n    <testcase name=\"broken.http\" classname=\"broken.http\" time=\"0.000\">\n      <error message=\"Failed to parse file: &quot;broken.http&quot;\" type=\"FileError\">"
+        ));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a < b && c > \"d\" 'e'"), "a &lt; b &amp;&amp; c &gt; &quot;d&quot; &apos;e&apos;");
+        assert_eq!(escape("line\n\tbinary\u{0}\u{1b}"), "line\n\tbinary");
+    }
+}
@@ -1,17 +1,47 @@
+pub mod assertion;
+pub mod body;
+pub mod chain;
 pub mod client;
 pub mod config;
+pub mod cookies;
+pub mod diagnostic;
 pub mod curl;
+pub mod dynamic;
 pub mod env;
 pub mod graphql;
+pub mod headers;
+pub mod html;
+pub mod jsonpath;
+pub mod junit;
+pub mod multipart;
 pub mod parser;
+pub mod report;
 pub mod rsocket;
+pub mod script;
+pub mod selection;
+pub mod summary;
+pub mod timing;
+pub mod tls;
 pub mod websocket;
+pub mod writer;
 
-pub use client::{HttpClient, HttpResponse};
-pub use config::{HttpClientConfig, ProxyConfig};
+pub use assertion::Assertion;
+pub use body::ResponseBody;
+pub use client::{HttpClient, HttpResponse, RedirectHop, SentRequest};
+pub use config::{HttpClientConfig, HttpVersion, ProxyConfig};
+pub use cookies::CookieJar;
+pub use diagnostic::{Diagnostic, Severity};
 pub use env::{Environment, EnvironmentManager, SslConfiguration};
-pub use parser::{parse_http_file, HttpRequest, Request, WebSocketRequest, WebSocketMessage, GraphQLRequest, RSocketRequest, RSocketMessage};
+pub use parser::{parse_http_file, read_http_file, FileVariable, HttpFile, HttpRequest, OutputFile, Request, RequestBody, RequestOptions, ScriptSource, Span, WebSocketRequest, WebSocketMessage, GraphQLRequest, RSocketRequest, RSocketMessage};
 pub use websocket::WebSocketClient;
 pub use rsocket::RSocketClient;
 pub use graphql::GraphQLClient;
+pub use headers::Headers;
 pub use curl::CurlConverter;
+pub use dynamic::DynamicVariables;
+pub use timing::Timings;
+pub use tls::ClientIdentity;
+pub use script::{ScriptOutput, TestResult};
+pub use selection::Selection;
+pub use summary::{Outcome, RequestResult, RunSummary};
+pub use report::Report;
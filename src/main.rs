@@ -1,11 +1,21 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
 use clap::{Parser, Subcommand};
+use regex::Regex;
 use rest_client::{
-    HttpClientConfig, CurlConverter, EnvironmentManager, GraphQLClient, HttpClient,
-    HttpRequest, Request, WebSocketClient, WebSocketRequest, GraphQLRequest,
-    RSocketClient, RSocketRequest,
+    ClientIdentity, CookieJar, HttpClientConfig, CurlConverter, DynamicVariables, EnvironmentManager, GraphQLClient, HttpClient,
+    HttpRequest, HttpResponse, Request, WebSocketClient, WebSocketRequest, GraphQLRequest,
+    RSocketClient, RSocketRequest, Selection, RunSummary, RequestResult, Outcome, Report, TestResult,
 };
-use std::path::PathBuf;
+use rest_client::graphql::GraphQLResponse;
+use rest_client::config::DEFAULT_MAX_REDIRECTS;
+use rest_client::parser::HttpFileParser;
+use rest_client::writer;
+use rest_client::timing::ConnectionTimer;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
 
 #[derive(Parser)]
 #[command(name = "rest-client")]
@@ -16,13 +26,16 @@ struct Cli {
 }
 
 #[derive(Subcommand)]
+// Parsed once per invocation, so boxing the `run` options would not pay off
+#[allow(clippy::large_enum_variant)]
 enum Commands {
-    /// Execute HTTP requests from a .http or .rest file
+    /// Execute HTTP requests from .http or .rest files
     Run {
-        /// Path to the .http or .rest file
-        file: PathBuf,
+        /// Paths to the .http or .rest files, run in order
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
         /// Environment name to use
-        #[arg(short, long)]
+        #[arg(long)]
         env: Option<String>,
         /// Path to environment file
         #[arg(short = 'e', long = "env-file")]
@@ -30,6 +43,63 @@ enum Commands {
         /// Path to private environment file
         #[arg(short = 'p', long = "private-env-file")]
         private_env_file: Option<PathBuf>,
+        /// Seed for $uuid and $random.* values (random if omitted)
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Freeze $timestamp and $isoTimestamp at this RFC 3339 time (the start of the run with --seed)
+        #[arg(long, value_parser = parse_rfc3339)]
+        now: Option<DateTime<Utc>>,
+        /// Cookie jar file (defaults to http-client.cookies next to the .http file)
+        #[arg(long = "cookie-jar")]
+        cookie_jar: Option<PathBuf>,
+        /// Redirects followed before a request fails
+        #[arg(long = "max-redirects", default_value_t = DEFAULT_MAX_REDIRECTS)]
+        max_redirects: usize,
+        /// Stop at the first redirect response instead of following it
+        #[arg(long = "no-redirect")]
+        no_redirect: bool,
+        /// Keep gzip, deflate, brotli and zstd response bodies compressed
+        #[arg(long = "no-decompress")]
+        no_decompress: bool,
+        /// Run only the request with this name
+        #[arg(long)]
+        name: Option<String>,
+        /// Run only the request at this line, counting from 1
+        #[arg(long)]
+        line: Option<usize>,
+        /// Run only requests whose name or URI matches this regex
+        #[arg(long)]
+        filter: Option<Regex>,
+        /// Run only requests with this `# @tag` (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Run the remaining requests after one fails to get a response
+        #[arg(long = "continue-on-error", overrides_with = "fail_fast")]
+        continue_on_error: bool,
+        /// Stop at the first request that fails to get a response (the default)
+        #[arg(long = "fail-fast", overrides_with = "continue_on_error")]
+        fail_fast: bool,
+        /// Count responses with a status outside 2xx as failed
+        #[arg(long = "fail-on-error-status")]
+        fail_on_error_status: bool,
+        /// Write a report after the run, e.g. junit:report.xml or html:report (repeatable)
+        #[arg(long = "report", value_name = "FORMAT:PATH")]
+        reports: Vec<Report>,
+    },
+    /// Check .http or .rest files for problems without running them
+    Lint {
+        /// Paths to the .http or .rest files
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Rewrite .http or .rest files in the canonical format
+    Fmt {
+        /// Paths to the .http or .rest files
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Report files that are not formatted instead of rewriting them
+        #[arg(long)]
+        check: bool,
     },
     /// Convert cURL command to HTTP request format
     Convert {
@@ -43,18 +113,91 @@ enum Commands {
     },
 }
 
+/// Exit codes besides 0 for success and 1 for any other error. 2 is used by
+/// clap for invalid arguments.
+const EXIT_PARSE_ERROR: u8 = 3;
+const EXIT_CHECKS_FAILED: u8 = 4;
+const EXIT_REQUEST_FAILED: u8 = 5;
+
+/// An error that ends the process with a specific exit code.
+#[derive(Debug)]
+struct Failure {
+    code: u8,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: u8, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    match cli.command {
+    match run_command(cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            // The same output as returning the error from `main`
+            eprintln!("Error: {:?}", error);
+            ExitCode::from(error.downcast_ref::<Failure>().map_or(1, |failure| failure.code))
+        }
+    }
+}
+
+async fn run_command(command: Commands) -> Result<()> {
+    match command {
         Commands::Run {
-            file,
+            files,
             env,
             env_file,
             private_env_file,
+            seed,
+            now,
+            cookie_jar,
+            max_redirects,
+            no_redirect,
+            no_decompress,
+            name,
+            line,
+            filter,
+            tags,
+            continue_on_error,
+            // The default; `continue_on_error` is false when it is given last
+            fail_fast: _,
+            fail_on_error_status,
+            reports,
         } => {
-            run_requests(file, env, env_file, private_env_file).await?;
+            let options = RunOptions {
+                // Chosen once, so every file of the run can be replayed with the printed values
+                dynamic: Arc::new(dynamic_variables(seed, now)),
+                cookie_jar,
+                max_redirects,
+                no_redirect,
+                no_decompress,
+                selection: Selection { name, line, filter, tags },
+                continue_on_error,
+                fail_on_error_status,
+            };
+            run_files(&files, env, env_file, private_env_file, &options, &reports).await?;
+        }
+        Commands::Lint { files } => {
+            lint_files(&files)?;
+        }
+        Commands::Fmt { files, check } => {
+            format_files(&files, check)?;
         }
         Commands::Convert { curl } => {
             let http = CurlConverter::curl_to_http(&curl)
@@ -73,18 +216,111 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn run_requests(
-    file: PathBuf,
+/// Seeds dynamic variables for a run. The clock is frozen only when `--now`
+/// or `--seed` is given: a replayed run also needs a replayable clock.
+fn dynamic_variables(seed: Option<u64>, now: Option<DateTime<Utc>>) -> DynamicVariables {
+    // Millisecond precision, as printed
+    let now = now.or_else(|| seed.map(|_| Utc::now().trunc_subsecs(3)));
+    DynamicVariables::with_seed(seed.unwrap_or_else(rand::random), now)
+}
+
+/// Options that control a single `run` invocation.
+struct RunOptions {
+    /// Shared by all files, so random values don't repeat from one file to the next
+    dynamic: Arc<DynamicVariables>,
+    cookie_jar: Option<PathBuf>,
+    max_redirects: usize,
+    no_redirect: bool,
+    no_decompress: bool,
+    /// Requests of the file to run
+    selection: Selection,
+    /// Run the remaining requests after one fails
+    continue_on_error: bool,
+    /// Count non-2xx responses as failed checks
+    fail_on_error_status: bool,
+}
+
+fn parse_rfc3339(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| format!("expected an RFC 3339 time such as 2024-01-01T00:00:00Z: {}", e))
+}
+
+fn rfc3339(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Runs each file, writes the reports and fails with the exit code for the
+/// worst outcome. Unless `continue_on_error` is set, a request without a
+/// response stops the run, including the remaining files.
+async fn run_files(
+    files: &[PathBuf],
     env_name: Option<String>,
     env_file: Option<PathBuf>,
     private_env_file: Option<PathBuf>,
+    options: &RunOptions,
+    reports: &[Report],
 ) -> Result<()> {
-    // Load environment files
+    let mut summaries = Vec::new();
+    // Exit codes of the files that could not be run
+    let mut file_errors = Vec::new();
+    for (idx, file) in files.iter().enumerate() {
+        if idx > 0 {
+            println!("\n{}\n", "#".repeat(80));
+        }
+        let summary = match run_requests(file, env_name.clone(), env_file.clone(), private_env_file.clone(), options).await {
+            Ok(summary) => summary,
+            Err(error) => {
+                // Reported as a file with an error, so the reports are still written
+                eprintln!("Error: {:?}", error);
+                file_errors.push(error.downcast_ref::<Failure>().map_or(1, |failure| failure.code));
+                let mut summary = RunSummary::new(file);
+                summary.environment = env_name.clone();
+                summary.error = Some(format!("{:#}", error));
+                // Masked like the results, if the environment loads at all
+                if let Ok(env_manager) = load_environment(file, env_file.as_deref(), private_env_file.as_deref()) {
+                    summary.mask(&env_manager.secrets(env_name.as_deref().unwrap_or("default")));
+                }
+                summary
+            }
+        };
+        let stop = summary.errors() > 0 && !options.continue_on_error;
+        summaries.push(summary);
+        if stop {
+            break;
+        }
+    }
+
+    for report in reports {
+        report.write(&summaries)?;
+    }
+
+    if let Some(&code) = file_errors.first() {
+        return Err(Failure::new(code, format!("{} file(s) could not be run", file_errors.len())).into());
+    }
+
+    if !options.selection.is_empty() && summaries.iter().all(|summary| summary.results.is_empty()) {
+        anyhow::bail!("No requests match {}", options.selection);
+    }
+    // A request without a response outweighs failed checks
+    let errors: usize = summaries.iter().map(RunSummary::errors).sum();
+    if errors > 0 {
+        return Err(Failure::new(EXIT_REQUEST_FAILED, format!("{} request(s) failed", errors)).into());
+    }
+    let failed_checks: usize = summaries.iter().map(|summary| summary.failed_checks().count()).sum();
+    if failed_checks > 0 {
+        return Err(Failure::new(EXIT_CHECKS_FAILED, format!("{} check(s) failed", failed_checks)).into());
+    }
+    Ok(())
+}
+
+/// Loads the environment files for `file`, the given ones or those next to it.
+fn load_environment(file: &Path, env_file: Option<&Path>, private_env_file: Option<&Path>) -> Result<EnvironmentManager> {
     let base_path = file.parent().unwrap_or(std::path::Path::new("."));
     let mut env_manager = EnvironmentManager::new(base_path);
 
     // Load private environment file first (highest priority)
-    if let Some(ref path) = private_env_file {
+    if let Some(path) = private_env_file {
         env_manager.load_private_env(path)?;
     } else {
         // Try default private env file
@@ -95,7 +331,7 @@ async fn run_requests(
     }
 
     // Load public environment file
-    if let Some(ref path) = env_file {
+    if let Some(path) = env_file {
         env_manager.load_env_file(path)?;
     } else {
         // Try default env file
@@ -104,62 +340,243 @@ async fn run_requests(
             env_manager.load_env_file(&default_env)?;
         }
     }
+    Ok(env_manager)
+}
+
+/// Runs the selected requests of `file` and prints a summary of them.
+async fn run_requests(
+    file: &Path,
+    env_name: Option<String>,
+    env_file: Option<PathBuf>,
+    private_env_file: Option<PathBuf>,
+    options: &RunOptions,
+) -> Result<RunSummary> {
+    let base_path = file.parent().unwrap_or(std::path::Path::new("."));
+    let mut env_manager = load_environment(file, env_file.as_deref(), private_env_file.as_deref())?;
 
     // Parse HTTP file
-    let requests = rest_client::parse_http_file(&file)
-        .with_context(|| format!("Failed to parse file: {:?}", file))?;
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read file: {:?}", file))?;
+    let http_file = HttpFileParser::new(content)
+        .with_file(file)
+        .parse_file()
+        .map_err(|e| e.context(Failure::new(EXIT_PARSE_ERROR, format!("Failed to parse file: {:?}", file))))?;
+    for diagnostic in &http_file.diagnostics {
+        eprintln!("{}", diagnostic);
+    }
 
-    if requests.is_empty() {
+    let mut summary = RunSummary::new(file);
+    summary.environment = env_name.clone();
+    if http_file.requests.is_empty() {
         println!("No requests found in file");
-        return Ok(());
+        return Ok(summary);
+    }
+    let requests = options.selection.select(&http_file.requests);
+    if requests.is_empty() {
+        println!("No requests in {:?} match {}", file, options.selection);
+        return Ok(summary);
+    }
+
+    // Seed dynamic variables and record how to replay this run
+    env_manager.set_dynamic_variables(options.dynamic.clone());
+    match options.dynamic.frozen_now() {
+        Some(now) => println!("Seed: {}, clock frozen at {}\n", options.dynamic.seed(), rfc3339(now)),
+        None => println!("Seed: {}\n", options.dynamic.seed()),
     }
 
     // Build client config
-    let mut client_config = HttpClientConfig::new();
+    let mut client_config = HttpClientConfig::new().with_max_redirects(options.max_redirects);
+    if options.no_redirect {
+        client_config = client_config.without_redirects();
+    }
+    if options.no_decompress {
+        client_config = client_config.without_decompression();
+    }
 
     // Apply SSL config from environment if available
     let env_name_str = env_name.as_deref().unwrap_or("default");
     if let Some(ssl_config) = env_manager.get_ssl_config(env_name_str) {
         client_config = client_config.with_ssl_config(ssl_config.clone());
+        // Load the client certificate once, so a passphrase is asked for once
+        if let Some(identity) = ClientIdentity::load(ssl_config, base_path)? {
+            client_config = client_config.with_client_identity(identity);
+        }
     }
 
+    // Cookies persist across requests and runs
+    let cookie_jar_path = options.cookie_jar.clone().unwrap_or_else(|| base_path.join("http-client.cookies"));
+    let cookie_jar = std::sync::Arc::new(CookieJar::load(&cookie_jar_path)?);
+    client_config = client_config.with_cookie_jar(cookie_jar.clone());
+
     // Create HTTP client
     let http_client = HttpClient::new(client_config.clone(), env_manager.clone(), base_path)?;
-    let ws_client = WebSocketClient::new(env_manager.clone());
-    let rsocket_client = RSocketClient::new(env_manager.clone());
+    let tls_connector = client_config.build_tls_connector(base_path)?;
+    let ws_client = WebSocketClient::new(env_manager.clone()).with_tls_connector(tls_connector.clone());
+    let rsocket_client = RSocketClient::new(env_manager.clone()).with_tls_connector(tls_connector);
+    let connection_timer = ConnectionTimer::new();
     let graphql_client = GraphQLClient::new(
-        client_config.build_client(base_path)?,
+        client_config.clone().with_connection_timer(connection_timer.clone()).build_client(base_path)?,
         env_manager.clone(),
-    );
+    )
+    .with_connection_timer(connection_timer);
 
     // Execute each request
     for (idx, request) in requests.iter().enumerate() {
+        if summary.errors() > 0 && !options.continue_on_error {
+            summary.push(RequestResult::new(request, Outcome::Skipped));
+            continue;
+        }
         if idx > 0 {
             println!("\n{}\n", "=".repeat(80));
         }
+        // In-file variables as defined up to this request
+        if let Some(index) = http_file.requests.iter().position(|r| std::ptr::eq(r, *request)) {
+            env_manager.set_file_variables(&http_file.variables_for(index));
+        }
 
-        match request {
+        let started = Instant::now();
+        let result = match request {
             Request::Http(http_req) => {
                 if let Some(name) = &http_req.name {
                     println!("### {}\n", name);
                 }
-                execute_http_request(&http_client, http_req, env_name.as_deref()).await?;
+                execute_http_request(&http_client, http_req, env_name.as_deref())
+                    .await
+                    .map(|response| {
+                        if let Some(name) = &http_req.name {
+                            env_manager.record_response(name.clone(), response.clone());
+                        }
+                        let mut result = RequestResult::new(request, Outcome::Passed).with_http_response(&response);
+                        if options.fail_on_error_status && !(200..300).contains(&response.status) {
+                            result.tests.push(TestResult {
+                                name: "status is 2xx".to_string(),
+                                passed: false,
+                                message: Some(format!("status was {}", response.status)),
+                            });
+                        }
+                        let failed: Vec<_> = result.tests.iter().filter(|t| !t.passed).cloned().collect();
+                        if !failed.is_empty() {
+                            result.outcome = Outcome::Failed(failed);
+                        }
+                        result
+                    })
             }
             Request::WebSocket(ws_req) => {
-                println!("### WebSocket Request\n");
-                execute_websocket_request(&ws_client, ws_req, env_name.as_deref()).await?;
+                println!("### {}\n", ws_req.name.as_deref().unwrap_or("WebSocket Request"));
+                execute_websocket_request(&ws_client, ws_req, env_name.as_deref())
+                    .await
+                    .map(|()| RequestResult::new(request, Outcome::Passed).with_duration(started.elapsed()))
             }
             Request::RSocket(rs_req) => {
-                println!("### RSocket Request\n");
-                execute_rsocket_request(&rsocket_client, rs_req, env_name.as_deref()).await?;
+                println!("### {}\n", rs_req.name.as_deref().unwrap_or("RSocket Request"));
+                execute_rsocket_request(&rsocket_client, rs_req, env_name.as_deref())
+                    .await
+                    .map(|()| RequestResult::new(request, Outcome::Passed).with_duration(started.elapsed()))
             }
             Request::GraphQL(gql_req) => {
-                println!("### GraphQL Request\n");
-                execute_graphql_request(&graphql_client, gql_req, env_name.as_deref()).await?;
+                println!("### {}\n", gql_req.name.as_deref().unwrap_or("GraphQL Request"));
+                let result = execute_graphql_request(&graphql_client, gql_req, env_name.as_deref())
+                    .await
+                    .map(|response| RequestResult::new(request, Outcome::Passed).with_graphql_response(&response));
+                // A jar that can't be saved fails this request, not the run
+                let saved = cookie_jar.save();
+                result.and_then(|result| saved.map(|()| result))
+            }
+        };
+        summary.push(result.unwrap_or_else(|error| {
+            eprintln!("Error: {:?}", error);
+            RequestResult::new(request, Outcome::Error(format!("{:#}", error))).with_duration(started.elapsed())
+        }));
+    }
+
+    println!("\n{}\n", "=".repeat(80));
+    println!("{}", summary.render());
+
+    let failed_checks: Vec<_> = summary.failed_checks().collect();
+    if !failed_checks.is_empty() {
+        println!("\nFailed checks:");
+        for (request_name, test) in &failed_checks {
+            match &test.message {
+                Some(message) => println!("  {}: {}: {}", request_name, test.name, message),
+                None => println!("  {}: {}", request_name, test.name),
+            }
+        }
+    }
+
+    // Reports are shared, so keep private env values out of them
+    summary.mask(&env_manager.secrets(env_name_str));
+    Ok(summary)
+}
+
+/// Prints the problems found in `files` and fails if any of them is an error.
+fn lint_files(files: &[PathBuf]) -> Result<()> {
+    let mut errors = 0;
+    let mut warnings = 0;
+    for file in files {
+        let content = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read file: {:?}", file))?;
+        let mut parser = HttpFileParser::new(content.clone()).with_file(file);
+        // Errors are reported below along with the warnings
+        let _ = parser.parse();
+        for diagnostic in parser.diagnostics() {
+            println!("{}\n", diagnostic.render(&content));
+            if diagnostic.is_error() {
+                errors += 1;
+            } else {
+                warnings += 1;
             }
         }
     }
 
+    println!("{} file(s) checked: {} error(s), {} warning(s)", files.len(), errors, warnings);
+    if errors > 0 {
+        return Err(Failure::new(EXIT_PARSE_ERROR, format!("{} error(s) found", errors)).into());
+    }
+    Ok(())
+}
+
+/// Rewrites `files` in the canonical format, or with `check` only lists the
+/// ones that would change.
+fn format_files(files: &[PathBuf], check: bool) -> Result<()> {
+    let mut unformatted = 0;
+    for file in files {
+        let content = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read file: {:?}", file))?;
+        let mut parser = HttpFileParser::new(content.clone()).with_file(file);
+        let parsed = parser.parse_file();
+        // Lines the parser ignores would be lost
+        if !parser.diagnostics().is_empty() {
+            for diagnostic in parser.diagnostics() {
+                println!("{}\n", diagnostic.render(&content));
+            }
+            let message = format!("Fix the problems in {:?} before formatting it", file);
+            return Err(Failure::new(EXIT_PARSE_ERROR, message).into());
+        }
+        let mut http_file = parsed?;
+        writer::normalize(&mut http_file);
+        let formatted = writer::write_file(&http_file);
+        if formatted == content {
+            continue;
+        }
+        if !writer::round_trips(&http_file, &formatted) {
+            anyhow::bail!("Formatting {:?} would change its requests, so it was left unchanged", file);
+        }
+        if !writer::keeps_comments(&content, &formatted) {
+            anyhow::bail!("Formatting {:?} would drop comments, so it was left unchanged", file);
+        }
+
+        if check {
+            println!("Not formatted: {}", file.display());
+            unformatted += 1;
+        } else {
+            std::fs::write(file, formatted).with_context(|| format!("Failed to write file: {:?}", file))?;
+            println!("Formatted {}", file.display());
+        }
+    }
+
+    if unformatted > 0 {
+        anyhow::bail!("{} file(s) are not formatted, run `rest-client fmt` to fix them", unformatted);
+    }
     Ok(())
 }
 
@@ -167,8 +584,17 @@ async fn execute_http_request(
     client: &HttpClient,
     request: &HttpRequest,
     env_name: Option<&str>,
-) -> Result<()> {
+) -> Result<HttpResponse> {
     println!("{} {}", request.method, request.uri);
+    if request.options.no_log {
+        // Neither the request nor the response is printed, only the outcome
+        let response = client
+            .execute_request(request, env_name)
+            .await
+            .context("Failed to execute HTTP request")?;
+        println!("{:?} {} (not logged: @no-log)", response.version, response.status);
+        return Ok(response);
+    }
     if !request.headers.is_empty() {
         println!("Headers:");
         for (key, value) in &request.headers {
@@ -186,7 +612,7 @@ async fn execute_http_request(
         .context("Failed to execute HTTP request")?;
 
     client.print_response(&response);
-    Ok(())
+    Ok(response)
 }
 
 async fn execute_websocket_request(
@@ -217,7 +643,7 @@ async fn execute_graphql_request(
     client: &GraphQLClient,
     request: &GraphQLRequest,
     env_name: Option<&str>,
-) -> Result<()> {
+) -> Result<GraphQLResponse> {
     println!("Query:\n{}", request.query);
     if let Some(vars) = &request.variables {
         println!("Variables:\n{}", serde_json::to_string_pretty(vars)?);
@@ -230,5 +656,77 @@ async fn execute_graphql_request(
         .context("Failed to execute GraphQL request")?;
 
     client.print_response(&response);
-    Ok(())
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves an empty `200 OK` to every request on localhost.
+    fn serve() -> String {
+        use hyper::service::{make_service_fn, service_fn};
+
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(service_fn(|_| async {
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(hyper::Body::empty()))
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    fn options(dynamic: DynamicVariables) -> RunOptions {
+        RunOptions {
+            dynamic: Arc::new(dynamic),
+            cookie_jar: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            no_redirect: false,
+            no_decompress: false,
+            selection: Selection::default(),
+            continue_on_error: false,
+            fail_on_error_status: false,
+        }
+    }
+
+    /// The URL each file's first request was sent to.
+    async fn sent_urls(files: &[PathBuf], options: &RunOptions) -> Vec<String> {
+        let mut urls = Vec::new();
+        for file in files {
+            let summary = run_requests(file, None, None, None, options).await.unwrap();
+            urls.push(summary.results[0].request.as_ref().unwrap().url.clone());
+        }
+        urls
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_variables_differ_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = format!("###\nGET {}/{{{{$uuid}}}}?n={{{{$random.alphanumeric(8)}}}}\n", serve());
+        let files = [dir.path().join("first.http"), dir.path().join("second.http")];
+        for file in &files {
+            std::fs::write(file, &content).unwrap();
+        }
+
+        let seeded = || options(DynamicVariables::with_seed(42, Some(Utc::now())));
+        let urls = sent_urls(&files, &seeded()).await;
+        assert_ne!(urls[0], urls[1]);
+        // The same seed replays the same values, file after file
+        assert_eq!(sent_urls(&files, &seeded()).await, urls);
+    }
+
+    #[test]
+    fn test_dynamic_variables_clock() {
+        let now = parse_rfc3339("2024-01-01T00:00:00Z").unwrap();
+        assert_eq!(dynamic_variables(None, Some(now)).frozen_now(), Some(now));
+        assert_eq!(dynamic_variables(Some(42), Some(now)).frozen_now(), Some(now));
+        // A replayed run freezes the clock at its start
+        let replayed = dynamic_variables(Some(42), None);
+        assert_eq!(replayed.seed(), 42);
+        assert!(replayed.frozen_now().is_some());
+        // An ordinary run reads the real clock on each use
+        assert_eq!(dynamic_variables(None, None).frozen_now(), None);
+    }
 }
@@ -0,0 +1,240 @@
+//! `multipart/form-data` bodies: splits the body written in the `.http` file
+//! by its declared boundary and sends the parts as a `reqwest` form, loading
+//! `< ./file` parts from disk.
+
+use anyhow::{bail, Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::multipart::{Form, Part as FormPart};
+use std::path::Path;
+use tokio_util::io::ReaderStream;
+
+use crate::parser::RequestBody;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    pub headers: Vec<(String, String)>,
+    pub content: RequestBody,
+}
+
+impl Part {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the `name` and `filename` parameters of `Content-Disposition`.
+    pub fn disposition(&self) -> (Option<String>, Option<String>) {
+        let Some(value) = self.header("Content-Disposition") else {
+            return (None, None);
+        };
+        let mut name = None;
+        let mut filename = None;
+        for param in value.split(';').skip(1) {
+            let Some((key, value)) = param.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_matches('"').to_string();
+            match key.trim().to_lowercase().as_str() {
+                "name" => name = Some(value),
+                "filename" => filename = Some(value),
+                _ => {}
+            }
+        }
+        (name, filename)
+    }
+}
+
+/// Extracts the `boundary` parameter from a `multipart/form-data` content
+/// type, keeping its case.
+pub fn boundary(content_type: &str) -> Option<String> {
+    let (mime, params) = content_type.split_once(';')?;
+    if !mime.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.split(';').find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Splits `body` into parts delimited by `--boundary` lines and terminated by
+/// `--boundary--`. Each part has its own headers followed by a blank line.
+pub fn parse(body: &str, boundary: &str) -> Result<Vec<Part>> {
+    let delimiter = format!("--{}", boundary);
+    let closing = format!("--{}--", boundary);
+
+    let mut parts = Vec::new();
+    let mut current: Option<Vec<&str>> = None;
+    let mut closed = false;
+
+    for line in body.lines() {
+        let trimmed = line.trim_end();
+        if trimmed == delimiter || trimmed == closing {
+            if let Some(lines) = current.take() {
+                parts.push(parse_part(&lines)?);
+            }
+            if trimmed == closing {
+                closed = true;
+                break;
+            }
+            current = Some(Vec::new());
+        } else if let Some(lines) = current.as_mut() {
+            lines.push(line);
+        }
+    }
+
+    if !closed {
+        bail!("Multipart body is missing the closing --{}-- line", boundary);
+    }
+    Ok(parts)
+}
+
+fn parse_part(lines: &[&str]) -> Result<Part> {
+    let mut headers = Vec::new();
+    let mut rest = lines;
+    while let Some((line, tail)) = rest.split_first() {
+        rest = tail;
+        if line.trim().is_empty() {
+            break;
+        }
+        let (key, value) = line
+            .split_once(':')
+            .with_context(|| format!("Invalid multipart part header: {}", line))?;
+        headers.push((key.trim().to_string(), value.trim().to_string()));
+    }
+
+    let content: Vec<String> = rest.iter().map(|line| line.to_string()).collect();
+    Ok(Part {
+        headers,
+        content: RequestBody::from_lines(&content),
+    })
+}
+
+/// Builds the form sent for `parts`. File paths are resolved relative to
+/// `base_path`, and `resolve` substitutes variables in `<@` templates.
+pub async fn build_form(parts: Vec<Part>, base_path: &Path, resolve: impl Fn(&str) -> String) -> Result<Form> {
+    let mut form = Form::new().percent_encode_noop();
+
+    for part in parts {
+        let (Some(name), filename) = part.disposition() else {
+            bail!("Multipart part is missing Content-Disposition with a name");
+        };
+
+        let mut form_part = match &part.content {
+            RequestBody::Inline(text) => FormPart::text(text.clone()),
+            RequestBody::TemplateFile(path) => {
+                let path = base_path.join(path);
+                let template = tokio::fs::read_to_string(&path)
+                    .await
+                    .with_context(|| format!("Failed to read multipart file: {}", path.display()))?;
+                FormPart::text(resolve(&template))
+            }
+            RequestBody::File(path) => {
+                let path = base_path.join(path);
+                let file = tokio::fs::File::open(&path)
+                    .await
+                    .with_context(|| format!("Failed to open multipart file: {}", path.display()))?;
+                let length = file.metadata().await?.len();
+                FormPart::stream_with_length(reqwest::Body::wrap_stream(ReaderStream::new(file)), length)
+            }
+        };
+
+        if let Some(filename) = filename {
+            form_part = form_part.file_name(filename);
+        }
+
+        let mut headers = HeaderMap::new();
+        for (key, value) in &part.headers {
+            if key.eq_ignore_ascii_case("Content-Disposition") {
+                continue;
+            }
+            if key.eq_ignore_ascii_case("Content-Type") {
+                form_part = form_part
+                    .mime_str(value)
+                    .with_context(|| format!("Invalid multipart Content-Type: {}", value))?;
+                continue;
+            }
+            headers.append(
+                HeaderName::from_bytes(key.as_bytes()).with_context(|| format!("Invalid header name: {}", key))?,
+                HeaderValue::from_str(value).with_context(|| format!("Invalid header value: {}", value))?,
+            );
+        }
+        if !headers.is_empty() {
+            form_part = form_part.headers(headers);
+        }
+
+        form = form.part(name, form_part);
+    }
+
+    Ok(form)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The body of the `mptr` live template
+    const MPTR: &str = "--WebAppBoundary
+Content-Disposition: form-data; name=\"element-name\"
+Content-Type: text/plain
+
+Name
+--WebAppBoundary
+Content-Disposition: form-data; name=\"data\"; filename=\"data.json\"
+Content-Type: application/json
+
+< ./request-form-data.json
+--WebAppBoundary--";
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(
+            boundary("multipart/form-data; boundary=WebAppBoundary"),
+            Some("WebAppBoundary".to_string())
+        );
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"Mixed Case\""),
+            Some("Mixed Case".to_string())
+        );
+        assert_eq!(boundary("multipart/form-data"), None);
+        assert_eq!(boundary("application/json; boundary=x"), None);
+    }
+
+    #[test]
+    fn test_parse_mptr_template() {
+        let parts = parse(MPTR, "WebAppBoundary").unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].disposition(), (Some("element-name".to_string()), None));
+        assert_eq!(parts[0].header("content-type"), Some("text/plain"));
+        assert_eq!(parts[0].content, RequestBody::Inline("Name".to_string()));
+        assert_eq!(
+            parts[1].disposition(),
+            (Some("data".to_string()), Some("data.json".to_string()))
+        );
+        assert_eq!(parts[1].content, RequestBody::File("./request-form-data.json".to_string()));
+    }
+
+    #[test]
+    fn test_parse_multiline_part_and_crlf() {
+        let body = "--b\r\nContent-Disposition: form-data; name=\"text\"\r\n\r\nline 1\r\n\r\nline 2\r\n--b--\r\n";
+        let parts = parse(body, "b").unwrap();
+        assert_eq!(parts[0].content, RequestBody::Inline("line 1\n\nline 2".to_string()));
+    }
+
+    #[test]
+    fn test_parse_invalid_bodies() {
+        assert!(parse("--b\nContent-Disposition: form-data; name=\"a\"\n\nvalue\n", "b").is_err());
+        assert!(parse("--b\nnot a header\n\nvalue\n--b--", "b").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build_form_requires_name() {
+        let parts = parse("--b\nContent-Type: text/plain\n\nvalue\n--b--", "b").unwrap();
+        assert!(build_form(parts, Path::new("."), |s| s.to_string()).await.is_err());
+    }
+}
@@ -1,53 +1,252 @@
 use anyhow::{Context, Result};
-use std::collections::HashMap;
-use std::path::Path;
-
-#[derive(Debug, Clone)]
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::assertion::Assertion;
+use crate::config::HttpVersion;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::headers::Headers;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct HttpRequest {
     pub name: Option<String>,
     pub method: String,
     pub uri: String,
     pub http_version: Option<String>,
-    pub headers: HashMap<String, String>,
-    pub body: Option<String>,
+    pub headers: Headers,
+    pub body: Option<RequestBody>,
     pub comments: Vec<String>,
+    /// Comment lines between the headers, each with the number of headers
+    /// before it
+    pub header_comments: Vec<(usize, String)>,
+    pub pre_request_scripts: Vec<ScriptSource>,
+    pub response_handlers: Vec<ScriptSource>,
+    pub assertions: Vec<Assertion>,
+    pub options: RequestOptions,
+    /// `>> path` or `>>! path` after the request
+    pub output: Option<OutputFile>,
+    /// From `# @tag` directives
+    pub tags: Vec<String>,
+    pub span: Span,
+}
+
+/// Lines of the file a request was parsed from, 1-based and inclusive. Starts
+/// at the `###` line and ends at the last non-blank line of the request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl Span {
+    pub fn contains(&self, line: usize) -> bool {
+        (self.start_line..=self.end_line).contains(&line)
+    }
+}
+
+/// File the response body is saved to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputFile {
+    pub path: String,
+    /// `>>!` replaces an existing file; `>>` picks a new name instead
+    pub overwrite: bool,
+}
+
+impl OutputFile {
+    /// Parses a `>> path` or `>>! path` line.
+    fn parse(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix(">>")?;
+        let (rest, overwrite) = match rest.strip_prefix('!') {
+            Some(rest) => (rest, true),
+            None => (rest, false),
+        };
+        let path = rest.trim();
+        (!path.is_empty()).then(|| Self {
+            path: path.to_string(),
+            overwrite,
+        })
+    }
+}
+
+/// Per-request settings given with `# @directive` comments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestOptions {
+    /// `# @no-cookie-jar`: neither send nor store cookies
+    pub no_cookie_jar: bool,
+    /// `# @no-redirect`: return redirect responses instead of following them
+    pub no_redirect: bool,
+    /// `# @max-redirects 3`: redirects followed before the request fails
+    pub max_redirects: Option<usize>,
+    /// `# @timeout 10`: time allowed for the whole response
+    pub timeout: Option<Duration>,
+    /// `# @connection-timeout 2 m`: time allowed to establish the connection
+    pub connection_timeout: Option<Duration>,
+    /// `# @no-log`: don't print the request and response
+    pub no_log: bool,
+}
+
+impl RequestOptions {
+    fn apply(&mut self, option: RequestOption) {
+        match option {
+            RequestOption::NoCookieJar => self.no_cookie_jar = true,
+            RequestOption::NoRedirect => self.no_redirect = true,
+            RequestOption::MaxRedirects(max) => self.max_redirects = Some(max),
+            RequestOption::Timeout(timeout) => self.timeout = Some(timeout),
+            RequestOption::ConnectionTimeout(timeout) => self.connection_timeout = Some(timeout),
+            RequestOption::NoLog => self.no_log = true,
+        }
+    }
+}
+
+enum RequestOption {
+    NoCookieJar,
+    NoRedirect,
+    MaxRedirects(usize),
+    Timeout(Duration),
+    ConnectionTimeout(Duration),
+    NoLog,
 }
 
-#[derive(Debug, Clone)]
+/// Where a request body comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestBody {
+    /// Body text written in the request
+    Inline(String),
+    /// `< path`: the file is sent unchanged
+    File(String),
+    /// `<@ path`: `{{variables}}` in the file are substituted first
+    TemplateFile(String),
+}
+
+impl RequestBody {
+    /// Returns the body text if it is written inline.
+    pub fn as_inline(&self) -> Option<&str> {
+        match self {
+            RequestBody::Inline(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Parses a body consisting of a single `< path` or `<@ path` line. The
+    /// marker must be followed by whitespace so inline XML stays inline.
+    pub(crate) fn from_lines(lines: &[String]) -> Self {
+        if let [line] = lines {
+            let line = line.trim();
+            let file_path = |marker: &str| {
+                line.strip_prefix(marker)
+                    .filter(|rest| rest.starts_with(char::is_whitespace))
+                    .map(|rest| rest.trim().to_string())
+            };
+            if let Some(path) = file_path("<@") {
+                return RequestBody::TemplateFile(path);
+            }
+            if let Some(path) = file_path("<") {
+                return RequestBody::File(path);
+            }
+        }
+        RequestBody::Inline(lines.join("\n"))
+    }
+}
+
+impl std::fmt::Display for RequestBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestBody::Inline(text) => write!(f, "{}", text),
+            RequestBody::File(path) => write!(f, "< {}", path),
+            RequestBody::TemplateFile(path) => write!(f, "<@ {}", path),
+        }
+    }
+}
+
+/// A JavaScript handler, either inline (`{% ... %}`) or a path to a script file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptSource {
+    Inline(String),
+    File(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct WebSocketRequest {
+    /// From the `###` line above the request
+    pub name: Option<String>,
+    /// From `# @tag` directives above the request line
+    pub tags: Vec<String>,
+    /// Comment lines above the request line
+    pub comments: Vec<String>,
+    pub span: Span,
     pub uri: String,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
+    /// Comment lines between the headers, see `HttpRequest::header_comments`
+    pub header_comments: Vec<(usize, String)>,
     pub messages: Vec<WebSocketMessage>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct WebSocketMessage {
     pub content: String,
     pub wait_for_server: usize,
+    /// Comment lines among the message, which are not sent
+    pub comments: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GraphQLRequest {
+    /// From the `###` line above the request
+    pub name: Option<String>,
+    /// From `# @tag` directives above the request line
+    pub tags: Vec<String>,
+    /// Comment lines above the request line
+    pub comments: Vec<String>,
+    pub span: Span,
     pub uri: String,
     pub query: String,
+    /// Comment lines among the query and variables, which are not sent
+    pub query_comments: Vec<String>,
     pub variables: Option<serde_json::Value>,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
+    /// Comment lines between the headers, see `HttpRequest::header_comments`
+    pub header_comments: Vec<(usize, String)>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RSocketRequest {
+    /// From the `###` line above the request
+    pub name: Option<String>,
+    /// From `# @tag` directives above the request line
+    pub tags: Vec<String>,
+    /// Comment lines above the request line
+    pub comments: Vec<String>,
+    pub span: Span,
     pub uri: String,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
+    /// Comment lines between the headers, see `HttpRequest::header_comments`
+    pub header_comments: Vec<(usize, String)>,
     pub messages: Vec<RSocketMessage>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RSocketMessage {
     pub content: String,
     pub wait_for_server: usize,
+    /// Comment lines among the message, which are not sent
+    pub comments: Vec<String>,
+}
+
+/// Words that start a request line rather than name a request
+pub(crate) const METHODS: &[&str] = &["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS", "WEBSOCKET", "GRAPHQL", "RSOCKET"];
+
+/// A `# @directive argument` comment line
+enum Directive {
+    Name(String),
+    Tags(Vec<String>),
+    Assert(Assertion),
+    Option(RequestOption),
+    /// Unknown or invalid, with the problem to report
+    Ignored(Diagnostic),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+// Requests are parsed once per file, so boxing the HTTP variant would not pay off
+#[allow(clippy::large_enum_variant)]
 pub enum Request {
     Http(HttpRequest),
     WebSocket(WebSocketRequest),
@@ -55,23 +254,161 @@ pub enum Request {
     RSocket(RSocketRequest),
 }
 
+impl Request {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Request::Http(request) => request.name.as_deref(),
+            Request::WebSocket(request) => request.name.as_deref(),
+            Request::GraphQL(request) => request.name.as_deref(),
+            Request::RSocket(request) => request.name.as_deref(),
+        }
+    }
+
+    pub fn uri(&self) -> &str {
+        match self {
+            Request::Http(request) => &request.uri,
+            Request::WebSocket(request) => &request.uri,
+            Request::GraphQL(request) => &request.uri,
+            Request::RSocket(request) => &request.uri,
+        }
+    }
+
+    pub fn tags(&self) -> &[String] {
+        match self {
+            Request::Http(request) => &request.tags,
+            Request::WebSocket(request) => &request.tags,
+            Request::GraphQL(request) => &request.tags,
+            Request::RSocket(request) => &request.tags,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Request::Http(request) => request.span,
+            Request::WebSocket(request) => request.span,
+            Request::GraphQL(request) => request.span,
+            Request::RSocket(request) => request.span,
+        }
+    }
+
+    /// Comment lines above the request line.
+    fn comments_mut(&mut self) -> &mut Vec<String> {
+        match self {
+            Request::Http(request) => &mut request.comments,
+            Request::WebSocket(request) => &mut request.comments,
+            Request::GraphQL(request) => &mut request.comments,
+            Request::RSocket(request) => &mut request.comments,
+        }
+    }
+
+    fn with_span(mut self, span: Span) -> Self {
+        match &mut self {
+            Request::Http(request) => request.span = span,
+            Request::WebSocket(request) => request.span = span,
+            Request::GraphQL(request) => request.span = span,
+            Request::RSocket(request) => request.span = span,
+        }
+        self
+    }
+}
+
+/// An in-file `@name = value` variable. It applies to the requests from
+/// `first_request` on, until the name is defined again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileVariable {
+    pub name: String,
+    pub value: String,
+    /// Index into `HttpFile::requests` of the first request that sees it
+    pub first_request: usize,
+}
+
+/// A parsed `.http` file: its requests and in-file `@name = value` variables.
+#[derive(Debug, Clone, Default)]
+pub struct HttpFile {
+    pub requests: Vec<Request>,
+    /// In declaration order
+    pub variables: Vec<FileVariable>,
+    /// Comment lines outside of requests, such as a header at the top
+    pub comments: Vec<String>,
+    /// Comment lines under a `###` line without a request after the last request
+    pub trailing_comments: Vec<String>,
+    /// Warnings about lines that were ignored
+    pub diagnostics: Vec<Diagnostic>,
+}
+
 pub struct HttpFileParser {
-    content: String,
     current_line: usize,
     lines: Vec<String>,
+    variables: Vec<FileVariable>,
+    /// Number of requests parsed so far
+    parsed_requests: usize,
+    comments: Vec<String>,
+    /// Comment lines under a `###` line without a request, for the next request
+    pending_comments: Vec<String>,
+    /// File named in diagnostics
+    file: PathBuf,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl HttpFileParser {
     pub fn new(content: String) -> Self {
         let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
         Self {
-            content,
             current_line: 0,
             lines,
+            variables: Vec::new(),
+            parsed_requests: 0,
+            comments: Vec::new(),
+            pending_comments: Vec::new(),
+            file: PathBuf::new(),
+            diagnostics: Vec::new(),
         }
     }
 
+    pub fn with_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = file.into();
+        self
+    }
+
+    /// In-file variables found by the last `parse()`, in declaration order.
+    pub fn variables(&self) -> &[FileVariable] {
+        &self.variables
+    }
+
+    /// Errors and warnings found by the last `parse()`, in line order.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn parse_file(&mut self) -> Result<HttpFile> {
+        let requests = self.parse()?;
+        Ok(HttpFile {
+            requests,
+            variables: self.variables.clone(),
+            comments: self.comments.clone(),
+            trailing_comments: self.pending_comments.clone(),
+            diagnostics: self.diagnostics.clone(),
+        })
+    }
+
+    /// Parses all requests. Fails if any error was found; the errors and any
+    /// warnings are available from `diagnostics()` either way.
     pub fn parse(&mut self) -> Result<Vec<Request>> {
+        let requests = self.parse_requests();
+        self.diagnostics.sort_by_key(|d| (d.line, d.column));
+        let errors: Vec<String> = self
+            .diagnostics
+            .iter()
+            .filter(|d| d.is_error())
+            .map(|d| d.to_string())
+            .collect();
+        if !errors.is_empty() {
+            anyhow::bail!("{}", errors.join("\n"));
+        }
+        Ok(requests)
+    }
+
+    fn parse_requests(&mut self) -> Vec<Request> {
         let mut requests = Vec::new();
         
         while self.current_line < self.lines.len() {
@@ -81,22 +418,18 @@ impl HttpFileParser {
                 self.current_line += 1;
                 continue;
             }
+            let start = self.current_line;
 
             // Check for request separator
-            if line.starts_with("###") {
+            if let Some(rest) = line.strip_prefix("###") {
                 // Extract name from ### line if present
-                let name_from_separator = if line.len() > 3 {
-                    let rest = line[3..].trim();
-                    if !rest.is_empty() {
-                        // Check if the entire rest is a single HTTP method word
-                        let rest_upper = rest.to_uppercase();
-                        let is_single_method = ["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS", "WEBSOCKET", "GRAPHQL", "RSOCKET"]
-                            .contains(&rest_upper.as_str());
-                        if !is_single_method {
-                            Some(rest.to_string())
-                        } else {
-                            None
-                        }
+                let rest = rest.trim();
+                let name_from_separator = if !rest.is_empty() {
+                    // Check if the entire rest is a single HTTP method word
+                    let rest_upper = rest.to_uppercase();
+                    let is_single_method = METHODS.contains(&rest_upper.as_str());
+                    if !is_single_method {
+                        Some(rest.to_string())
                     } else {
                         None
                     }
@@ -104,43 +437,168 @@ impl HttpFileParser {
                     None
                 };
                 self.current_line += 1; // Skip the ### line
-                if let Some(request) = self.parse_request_with_name(name_from_separator)? {
-                    requests.push(request);
+                if let Some(request) = self.parse_request_with_name(name_from_separator) {
+                    requests.push(self.finish_request(request, start));
                 }
             } else if line.starts_with("WEBSOCKET") {
-                if let Some(ws_request) = self.parse_websocket()? {
-                    requests.push(Request::WebSocket(ws_request));
+                if let Some(ws_request) = self.parse_websocket() {
+                    requests.push(self.finish_request(Request::WebSocket(ws_request), start));
                 }
             } else if line.starts_with("RSOCKET") {
-                if let Some(rs_request) = self.parse_rsocket()? {
-                    requests.push(Request::RSocket(rs_request));
+                if let Some(rs_request) = self.parse_rsocket() {
+                    requests.push(self.finish_request(Request::RSocket(rs_request), start));
                 }
             } else if line.starts_with("GRAPHQL") {
-                if let Some(gql_request) = self.parse_graphql()? {
-                    requests.push(Request::GraphQL(gql_request));
+                if let Some(gql_request) = self.parse_graphql() {
+                    requests.push(self.finish_request(Request::GraphQL(gql_request), start));
                 }
+            } else if let Some((name, value)) = parse_file_variable(line) {
+                self.push_variable(name, value);
+                self.current_line += 1;
             } else {
+                if line.starts_with('#') || line.starts_with("//") {
+                    self.comments.push(line.to_string());
+                } else {
+                    let diagnostic = self.diagnostic(
+                        Severity::Warning,
+                        self.column(0),
+                        "Line is outside of a request and is ignored; start requests with `###`",
+                    );
+                    self.diagnostics.push(diagnostic);
+                }
                 self.current_line += 1;
             }
+            self.parsed_requests = requests.len();
         }
 
-        Ok(requests)
+        requests
+    }
+
+    /// Sets the span of a request parsed from line `start` (0-based) and moves
+    /// the comments of separators without a request above it into it.
+    fn finish_request(&mut self, mut request: Request, start: usize) -> Request {
+        let comments = request.comments_mut();
+        comments.splice(0..0, std::mem::take(&mut self.pending_comments));
+        request.with_span(self.span_from(start))
+    }
+
+    /// Records a variable for the request being parsed and those after it.
+    fn push_variable(&mut self, name: String, value: String) {
+        self.variables.push(FileVariable {
+            name,
+            value,
+            first_request: self.parsed_requests,
+        });
     }
 
-    fn parse_request(&mut self) -> Result<Option<Request>> {
-        self.parse_request_with_name(None)
+    /// The lines from `start` (0-based) to the last non-blank line parsed.
+    fn span_from(&self, start: usize) -> Span {
+        let end = (start..self.current_line)
+            .rev()
+            .find(|&index| !self.lines[index].trim().is_empty())
+            .unwrap_or(start);
+        Span {
+            start_line: start + 1,
+            end_line: end + 1,
+        }
+    }
+
+    /// A diagnostic for the current line. `column` is 1-based.
+    fn diagnostic(&self, severity: Severity, column: usize, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            file: self.file.clone(),
+            line: self.current_line + 1,
+            column,
+            severity,
+            message: message.into(),
+        }
+    }
+
+    /// Column of the byte `offset` into the trimmed current line.
+    fn column(&self, offset: usize) -> usize {
+        let line = &self.lines[self.current_line];
+        let trimmed = line.trim_start();
+        let indent = line[..line.len() - trimmed.len()].chars().count();
+        indent + trimmed[..offset.min(trimmed.len())].chars().count() + 1
+    }
+
+    /// Adds the `Name: value` header on the current line to `headers`.
+    fn parse_header(&mut self, headers: &mut Headers) {
+        let line = self.lines[self.current_line].trim();
+        let problem = match line.split_once(':') {
+            Some((name, _)) if name.trim().is_empty() => "Header name is empty",
+            Some((name, value)) => {
+                headers.append(name.trim(), value.trim());
+                return;
+            }
+            None => "Expected a header `Name: value`",
+        };
+        let diagnostic = self.diagnostic(Severity::Error, self.column(0), problem);
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Reports a `WEBSOCKET`, `GRAPHQL` or `RSOCKET` line without a URL and skips it.
+    fn missing_url(&mut self, keyword: &str) {
+        let column = self.column(self.lines[self.current_line].trim().len());
+        let diagnostic = self.diagnostic(Severity::Error, column, format!("Expected a URL after {}", keyword));
+        self.diagnostics.push(diagnostic);
+        self.current_line += 1;
+    }
+
+    /// Parses the JSON variables of a GraphQL request starting at the current
+    /// line. The block ends at the first line that completes a JSON value.
+    fn parse_graphql_variables(&mut self) -> Option<serde_json::Value> {
+        let start_line = self.current_line;
+        let mut var_lines = Vec::new();
+        while self.current_line < self.lines.len() {
+            let line = &self.lines[self.current_line];
+            let trimmed = line.trim();
+            if trimmed.starts_with("###") || trimmed.starts_with("WEBSOCKET") || trimmed.starts_with("GRAPHQL") || trimmed.starts_with("RSOCKET") {
+                break;
+            }
+            var_lines.push(line.clone());
+            self.current_line += 1;
+            if trimmed.ends_with('}') {
+                if let Ok(vars) = serde_json::from_str(&var_lines.join("\n")) {
+                    return Some(vars);
+                }
+            }
+        }
+
+        let e = match serde_json::from_str(&var_lines.join("\n")) {
+            Ok(vars) => return Some(vars),
+            Err(e) => e,
+        };
+        // serde_json positions are relative to the variables block
+        let message = e.to_string();
+        let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(m, _)| m);
+        self.diagnostics.push(Diagnostic {
+            file: self.file.clone(),
+            line: start_line + e.line(),
+            column: e.column().max(1),
+            severity: Severity::Error,
+            message: format!("Invalid GraphQL variables: {}", message),
+        });
+        None
     }
 
-    fn parse_request_with_name(&mut self, initial_name: Option<String>) -> Result<Option<Request>> {
+    fn parse_request_with_name(&mut self, initial_name: Option<String>) -> Option<Request> {
         let mut name = initial_name;
         let mut method = "GET".to_string();
         let mut uri = String::new();
         let mut http_version = None;
-        let mut headers = HashMap::new();
+        let mut headers = Headers::new();
         let mut body = None;
         let mut comments = Vec::new();
         let mut in_body = false;
-        let mut body_lines = Vec::new();
+        let mut body_lines: Vec<String> = Vec::new();
+        let mut header_comments = Vec::new();
+        let mut pre_request_scripts = Vec::new();
+        let mut response_handlers = Vec::new();
+        let mut assertions = Vec::new();
+        let mut options = RequestOptions::default();
+        let mut output = None;
+        let mut tags = Vec::new();
 
         // Parse request line
         // Note: The ### line was already consumed by parse()
@@ -151,10 +609,9 @@ impl HttpFileParser {
             let line = self.lines[self.current_line].trim();
             
             // Check if current line is a name (not a method, not a URL, not a header)
-            if !line.is_empty() && !line.starts_with("http") && !line.starts_with("//") && !line.starts_with("#") {
+            if !line.is_empty() && !line.starts_with("http") && !line.starts_with("//") && !line.starts_with("#") && !line.starts_with('<') && !line.starts_with('@') {
                 let first_word = line.split_whitespace().next().unwrap_or("").to_uppercase();
-                let is_method = ["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS", "WEBSOCKET", "GRAPHQL", "RSOCKET"]
-                    .contains(&first_word.as_str());
+                let is_method = METHODS.contains(&first_word.as_str());
                 if !is_method && !line.contains(':') && !line.contains("://") {
                     name = Some(line.to_string());
                     self.current_line += 1;
@@ -162,20 +619,58 @@ impl HttpFileParser {
             }
         }
 
-        // Check for @name annotation
+        // Check for @name annotation and pre-request scripts
         while self.current_line < self.lines.len() {
             let line = self.lines[self.current_line].trim();
-            if line.starts_with("# @name") {
-                name = Some(line[7..].trim().to_string());
+            if line.is_empty() {
                 self.current_line += 1;
-            } else if line.starts_with("//") || line.starts_with("#") {
-                if !line.starts_with("# @") {
-                    comments.push(line.to_string());
+            } else if line.starts_with("###") {
+                // Separator without a request, let parse() handle the next one
+                self.pending_comments.extend(comments);
+                return None;
+            } else if line.starts_with('<') {
+                if let Some(script) = self.parse_script('<') {
+                    pre_request_scripts.push(script);
                 }
+            } else if let Some((name, value)) = parse_file_variable(line) {
+                self.push_variable(name, value);
                 self.current_line += 1;
-                } else {
-                    break;
+            } else if let Some(directive) = self.parse_directive(line) {
+                match directive {
+                    Directive::Name(value) => name = Some(value),
+                    Directive::Tags(values) => tags.extend(values),
+                    Directive::Assert(assertion) => assertions.push(assertion),
+                    Directive::Option(option) => options.apply(option),
+                    Directive::Ignored(diagnostic) => {
+                        // Kept so the request can be written back out unchanged
+                        comments.push(line.to_string());
+                        self.diagnostics.push(diagnostic);
+                    }
                 }
+                self.current_line += 1;
+            } else if line.starts_with("//") || line.starts_with("#") {
+                comments.push(line.to_string());
+                self.current_line += 1;
+            } else {
+                break;
+            }
+        }
+
+        // WebSocket, GraphQL and RSocket requests under a `###` line
+        if let Some(line) = self.lines.get(self.current_line).map(|l| l.trim()) {
+            if line.starts_with("WEBSOCKET") {
+                return self
+                    .parse_websocket()
+                    .map(|request| Request::WebSocket(WebSocketRequest { name, tags, comments, ..request }));
+            } else if line.starts_with("RSOCKET") {
+                return self
+                    .parse_rsocket()
+                    .map(|request| Request::RSocket(RSocketRequest { name, tags, comments, ..request }));
+            } else if line.starts_with("GRAPHQL") {
+                return self
+                    .parse_graphql()
+                    .map(|request| Request::GraphQL(GraphQLRequest { name, tags, comments, ..request }));
+            }
         }
 
         // Parse method and URI
@@ -194,8 +689,23 @@ impl HttpFileParser {
                         if parts.len() > 1 {
                             uri = parts[1].to_string();
                             if parts.len() > 2 {
-                                http_version = Some(parts[2..].join(" "));
+                                let version = parts[2..].join(" ");
+                                if let Err(e) = HttpVersion::parse(&version) {
+                                    let after_uri = line.find(parts[1]).unwrap_or(0) + parts[1].len();
+                                    let rest = &line[after_uri..];
+                                    let column = self.column(after_uri + rest.len() - rest.trim_start().len());
+                                    let diagnostic = self.diagnostic(Severity::Error, column, e.to_string());
+                                    self.diagnostics.push(diagnostic);
+                                }
+                                http_version = Some(version);
                             }
+                        } else {
+                            let diagnostic = self.diagnostic(
+                                Severity::Error,
+                                self.column(line.len()),
+                                format!("Expected a URL after {}", method),
+                            );
+                            self.diagnostics.push(diagnostic);
                         }
                     }
                     self.current_line += 1;
@@ -212,20 +722,40 @@ impl HttpFileParser {
                 break;
             }
 
-            if line.starts_with("//") || line.starts_with("#") {
-                if !line.starts_with("# @") {
-                    comments.push(line.to_string());
+            // Next request right after the headers
+            if line.starts_with("###") {
+                break;
+            }
+
+            // Response handler or output file right after the headers, without a body
+            if is_response_handler(line) || line.starts_with(">>") {
+                in_body = true;
+                break;
+            }
+
+            if let Some(directive) = self.parse_directive(line) {
+                match directive {
+                    Directive::Name(value) => name = Some(value),
+                    Directive::Tags(values) => tags.extend(values),
+                    Directive::Assert(assertion) => assertions.push(assertion),
+                    Directive::Option(option) => options.apply(option),
+                    Directive::Ignored(diagnostic) => {
+                        // Kept so the request can be written back out unchanged
+                        header_comments.push((headers.len(), line.to_string()));
+                        self.diagnostics.push(diagnostic);
+                    }
                 }
                 self.current_line += 1;
                 continue;
             }
 
-            if let Some(colon_pos) = line.find(':') {
-                let key = line[..colon_pos].trim().to_string();
-                let value = line[colon_pos + 1..].trim().to_string();
-                headers.insert(key, value);
+            if line.starts_with("//") || line.starts_with("#") {
+                header_comments.push((headers.len(), line.to_string()));
+                self.current_line += 1;
+                continue;
             }
-            
+
+            self.parse_header(&mut headers);
             self.current_line += 1;
         }
 
@@ -246,20 +776,65 @@ impl HttpFileParser {
                     break;
                 }
 
-                body_lines.push(line.clone());
+                // Response handlers end the body
+                if is_response_handler(trimmed) {
+                    if let Some(handler) = self.parse_script('>') {
+                        response_handlers.push(handler);
+                    }
+                    continue;
+                }
+
+                // `>>` inside the body is body text; the output file follows
+                // a blank line or a handler
+                let after_body = !response_handlers.is_empty()
+                    || output.is_some()
+                    || body_lines.last().is_none_or(|l| l.trim().is_empty());
+                if trimmed.starts_with(">>") && after_body {
+                    let message = match OutputFile::parse(trimmed) {
+                        Some(_) if output.is_some() => Some("Only one output file is allowed; this one is ignored"),
+                        Some(file) => {
+                            output = Some(file);
+                            None
+                        }
+                        None => Some("Expected a file path after `>>`"),
+                    };
+                    if let Some(message) = message {
+                        let diagnostic = self.diagnostic(Severity::Warning, self.column(0), message);
+                        self.diagnostics.push(diagnostic);
+                    }
+                    self.current_line += 1;
+                    continue;
+                }
+
+                if response_handlers.is_empty() && output.is_none() {
+                    body_lines.push(line.clone());
+                } else if !trimmed.is_empty() {
+                    let message = if response_handlers.is_empty() {
+                        "Line after the output file is ignored; put the body before `>>`"
+                    } else {
+                        "Line after the response handler is ignored; put the body before `>` handlers"
+                    };
+                    let diagnostic = self.diagnostic(Severity::Warning, self.column(0), message);
+                    self.diagnostics.push(diagnostic);
+                }
                 self.current_line += 1;
             }
 
+            while body_lines.last().is_some_and(|l| l.trim().is_empty()) {
+                body_lines.pop();
+            }
+
             if !body_lines.is_empty() {
-                body = Some(body_lines.join("\n"));
+                body = Some(RequestBody::from_lines(&body_lines));
             }
         }
 
         if uri.is_empty() {
-            return Ok(None);
+            self.pending_comments.extend(comments);
+            return None;
         }
 
-        Ok(Some(Request::Http(HttpRequest {
+        Some(Request::Http(HttpRequest {
             name,
             method,
             uri,
@@ -267,111 +842,170 @@ impl HttpFileParser {
             headers,
             body,
             comments,
-        })))
+            header_comments,
+            pre_request_scripts,
+            response_handlers,
+            assertions,
+            options,
+            output,
+            tags,
+            span: Span::default(),
+        }))
     }
 
-    fn parse_websocket(&mut self) -> Result<Option<WebSocketRequest>> {
-        let line = self.lines[self.current_line].trim();
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        
-        if parts.len() < 2 {
-            return Ok(None);
-        }
-
-        let uri = parts[1].to_string();
-        self.current_line += 1;
+    /// Parses a `# @directive` or `// @directive` comment. Returns `None` for
+    /// lines that are not directives.
+    fn parse_directive(&self, line: &str) -> Option<Directive> {
+        let rest = line
+            .strip_prefix("//")
+            .or_else(|| line.strip_prefix('#'))
+            .and_then(|rest| rest.trim_start().strip_prefix('@'))?;
+
+        let (directive, argument) = match rest.find(char::is_whitespace) {
+            Some(pos) => (&rest[..pos], rest[pos..].trim()),
+            None => (rest, ""),
+        };
+
+        let parsed = match directive {
+            "name" => Ok(Directive::Name(argument.to_string())),
+            "tag" => parse_tags(argument).map(Directive::Tags),
+            "assert" => Assertion::parse(argument).map(Directive::Assert),
+            "no-cookie-jar" => Ok(Directive::Option(RequestOption::NoCookieJar)),
+            "no-redirect" => Ok(Directive::Option(RequestOption::NoRedirect)),
+            "no-log" => Ok(Directive::Option(RequestOption::NoLog)),
+            "max-redirects" => argument
+                .parse()
+                .map(|max| Directive::Option(RequestOption::MaxRedirects(max)))
+                .map_err(anyhow::Error::from),
+            "timeout" => parse_timeout(argument).map(|timeout| Directive::Option(RequestOption::Timeout(timeout))),
+            "connection-timeout" => {
+                parse_timeout(argument).map(|timeout| Directive::Option(RequestOption::ConnectionTimeout(timeout)))
+            }
+            _ => {
+                let column = self.column(line.find('@').unwrap_or(0));
+                let message = format!("Unknown directive @{} is ignored", directive);
+                return Some(Directive::Ignored(self.diagnostic(Severity::Warning, column, message)));
+            }
+        };
 
-        let mut headers = HashMap::new();
-        let mut messages = Vec::new();
-        let mut current_message = Vec::new();
-        let mut wait_count = 0;
+        Some(parsed.unwrap_or_else(|e| {
+            let column = self.column(line.len() - argument.len());
+            let message = format!("Invalid @{}: {}", directive, e);
+            Directive::Ignored(self.diagnostic(Severity::Error, column, message))
+        }))
+    }
 
-        // Parse headers
-        while self.current_line < self.lines.len() {
-            let line = self.lines[self.current_line].trim();
-            
-            if line.is_empty() {
-                self.current_line += 1;
-                break;
-            }
+    /// Parses a script introduced by `marker` (`<` or `>`) starting at the
+    /// current line, either an inline `{% ... %}` block or a file reference.
+    /// Leaves `current_line` on the line following the script.
+    fn parse_script(&mut self, marker: char) -> Option<ScriptSource> {
+        let line = self.lines[self.current_line].trim();
+        let rest = line.strip_prefix(marker)?.trim().to_string();
+        // Reported at the opening `{%` if the script runs to the end of the file
+        let column = self.column(line.find("{%").unwrap_or(0));
+        let unterminated = self.diagnostic(Severity::Error, column, "Script is not closed with `%}`");
+        self.current_line += 1;
 
-            if line.starts_with("//") || line.starts_with("#") {
-                self.current_line += 1;
-                continue;
-            }
+        let Some(inline) = rest.strip_prefix("{%") else {
+            return if rest.is_empty() { None } else { Some(ScriptSource::File(rest)) };
+        };
 
-            if let Some(colon_pos) = line.find(':') {
-                let key = line[..colon_pos].trim().to_string();
-                let value = line[colon_pos + 1..].trim().to_string();
-                headers.insert(key, value);
-            }
-            
-            self.current_line += 1;
+        if let Some(end) = inline.find("%}") {
+            return Some(ScriptSource::Inline(inline[..end].trim().to_string()));
         }
 
-        // Parse messages
+        let mut script_lines = vec![inline.to_string()];
+        let mut closed = false;
         while self.current_line < self.lines.len() {
-            let line = self.lines[self.current_line].trim();
-            
-            if line.starts_with("###") || line.starts_with("WEBSOCKET") || line.starts_with("GRAPHQL") || line.starts_with("RSOCKET") {
+            let line = &self.lines[self.current_line];
+            self.current_line += 1;
+            if let Some(end) = line.find("%}") {
+                script_lines.push(line[..end].to_string());
+                closed = true;
                 break;
             }
+            script_lines.push(line.clone());
+        }
+        if !closed {
+            self.diagnostics.push(unterminated);
+        }
 
-            if line == "===" || line.starts_with("=== wait-for-server") {
-                // Save current message if any
-                if !current_message.is_empty() {
-                    messages.push(WebSocketMessage {
-                        content: current_message.join("\n"),
-                        wait_for_server: wait_count,
-                    });
-                    current_message.clear();
-                }
-
-                // Count wait-for-server
-                if line.contains("wait-for-server") {
-                    wait_count += 1;
-                } else {
-                    wait_count = 0;
-                }
-            } else if !line.starts_with("//") && !line.starts_with("#") {
-                current_message.push(self.lines[self.current_line].clone());
-            }
+        Some(ScriptSource::Inline(script_lines.join("\n").trim().to_string()))
+    }
 
-            self.current_line += 1;
+    fn parse_websocket(&mut self) -> Option<WebSocketRequest> {
+        let line = self.lines[self.current_line].trim();
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        
+        if parts.len() < 2 {
+            self.missing_url("WEBSOCKET");
+            return None;
         }
 
-        // Add last message
-        if !current_message.is_empty() {
-            messages.push(WebSocketMessage {
-                content: current_message.join("\n"),
-                wait_for_server: wait_count,
-            });
-        }
+        let uri = parts[1].to_string();
+        self.current_line += 1;
 
-        Ok(Some(WebSocketRequest {
+        let (mut headers, mut header_comments) = self.parse_headers();
+        let messages = self.parse_messages(&mut headers, &mut header_comments);
+
+        Some(WebSocketRequest {
+            name: None,
+            tags: Vec::new(),
+            comments: Vec::new(),
+            span: Span::default(),
             uri,
             headers,
-            messages,
-        }))
+            header_comments,
+            messages: messages
+                .into_iter()
+                .map(|(content, wait_for_server, comments)| WebSocketMessage {
+                    content,
+                    wait_for_server,
+                    comments,
+                })
+                .collect(),
+        })
     }
 
-    fn parse_rsocket(&mut self) -> Result<Option<RSocketRequest>> {
+    fn parse_rsocket(&mut self) -> Option<RSocketRequest> {
         let line = self.lines[self.current_line].trim();
         let parts: Vec<&str> = line.split_whitespace().collect();
 
         if parts.len() < 2 {
-            return Ok(None);
+            self.missing_url("RSOCKET");
+            return None;
         }
 
         let uri = parts[1].to_string();
         self.current_line += 1;
 
-        let mut headers = HashMap::new();
-        let mut messages = Vec::new();
-        let mut current_message = Vec::new();
-        let mut wait_count = 0;
+        let (mut headers, mut header_comments) = self.parse_headers();
+        let messages = self.parse_messages(&mut headers, &mut header_comments);
 
-        // Parse headers
+        Some(RSocketRequest {
+            name: None,
+            tags: Vec::new(),
+            comments: Vec::new(),
+            span: Span::default(),
+            uri,
+            headers,
+            header_comments,
+            messages: messages
+                .into_iter()
+                .map(|(content, wait_for_server, comments)| RSocketMessage {
+                    content,
+                    wait_for_server,
+                    comments,
+                })
+                .collect(),
+        })
+    }
+
+    /// Parses the headers of a WebSocket, RSocket or GraphQL request up to the
+    /// blank line that ends them, with the comment lines between them.
+    fn parse_headers(&mut self) -> (Headers, Vec<(usize, String)>) {
+        let mut headers = Headers::new();
+        let mut header_comments = Vec::new();
         while self.current_line < self.lines.len() {
             let line = self.lines[self.current_line].trim();
 
@@ -380,21 +1014,36 @@ impl HttpFileParser {
                 break;
             }
 
+            if line.starts_with("###") {
+                break;
+            }
+
             if line.starts_with("//") || line.starts_with("#") {
+                header_comments.push((headers.len(), line.to_string()));
                 self.current_line += 1;
                 continue;
             }
 
-            if let Some(colon_pos) = line.find(':') {
-                let key = line[..colon_pos].trim().to_string();
-                let value = line[colon_pos + 1..].trim().to_string();
-                headers.insert(key, value);
-            }
-
+            self.parse_header(&mut headers);
             self.current_line += 1;
         }
+        (headers, header_comments)
+    }
+
+    /// Parses the `===`-separated messages of a WebSocket or RSocket request
+    /// as content, the number of `=== wait-for-server` lines before it and the
+    /// comment lines since the previous message. Comments after the last
+    /// message go with it, or after the headers if there is no message.
+    fn parse_messages(
+        &mut self,
+        headers: &mut Headers,
+        header_comments: &mut Vec<(usize, String)>,
+    ) -> Vec<(String, usize, Vec<String>)> {
+        let mut messages = Vec::new();
+        let mut current_message = Vec::new();
+        let mut comments = Vec::new();
+        let mut wait_count = 0;
 
-        // Parse messages
         while self.current_line < self.lines.len() {
             let line = self.lines[self.current_line].trim();
 
@@ -403,79 +1052,56 @@ impl HttpFileParser {
             }
 
             if line == "===" || line.starts_with("=== wait-for-server") {
-                if !current_message.is_empty() {
-                    messages.push(RSocketMessage {
-                        content: current_message.join("\n"),
-                        wait_for_server: wait_count,
-                    });
-                    current_message.clear();
+                // Save current message if any
+                if let Some(content) = message_content(&current_message) {
+                    messages.push((content, wait_count, std::mem::take(&mut comments)));
                 }
+                current_message.clear();
 
+                // Count wait-for-server
                 if line.contains("wait-for-server") {
                     wait_count += 1;
                 } else {
                     wait_count = 0;
                 }
-            } else if !line.starts_with("//") && !line.starts_with("#") {
+            } else if line.starts_with("//") || line.starts_with("#") {
+                comments.push(line.to_string());
+            } else {
                 current_message.push(self.lines[self.current_line].clone());
             }
 
             self.current_line += 1;
         }
 
-        if !current_message.is_empty() {
-            messages.push(RSocketMessage {
-                content: current_message.join("\n"),
-                wait_for_server: wait_count,
-            });
+        // Add last message
+        if let Some(content) = message_content(&current_message) {
+            messages.push((content, wait_count, comments));
+        } else if let Some((_, _, last)) = messages.last_mut() {
+            last.extend(comments);
+        } else {
+            header_comments.extend(comments.into_iter().map(|comment| (headers.len(), comment)));
         }
-
-        Ok(Some(RSocketRequest {
-            uri,
-            headers,
-            messages,
-        }))
+        messages
     }
 
-    fn parse_graphql(&mut self) -> Result<Option<GraphQLRequest>> {
+    fn parse_graphql(&mut self) -> Option<GraphQLRequest> {
         let line = self.lines[self.current_line].trim();
         let parts: Vec<&str> = line.split_whitespace().collect();
         
         if parts.len() < 2 {
-            return Ok(None);
+            self.missing_url("GRAPHQL");
+            return None;
         }
 
         let uri = parts[1].to_string();
         self.current_line += 1;
 
-        let mut headers = HashMap::new();
+        let (headers, header_comments) = self.parse_headers();
         let mut variables: Option<serde_json::Value> = None;
         let mut query_lines = Vec::new();
+        let mut query_comments = Vec::new();
         let mut in_variables = false;
 
-        // Parse headers
-        while self.current_line < self.lines.len() {
-            let line = self.lines[self.current_line].trim();
-            
-            if line.is_empty() {
-                self.current_line += 1;
-                break;
-            }
-
-            if line.starts_with("//") || line.starts_with("#") {
-                self.current_line += 1;
-                continue;
-            }
-
-            if let Some(colon_pos) = line.find(':') {
-                let key = line[..colon_pos].trim().to_string();
-                let value = line[colon_pos + 1..].trim().to_string();
-                headers.insert(key, value);
-            }
-            
-            self.current_line += 1;
-        }
-
         // Parse query and variables
         while self.current_line < self.lines.len() {
             let line = self.lines[self.current_line].trim();
@@ -485,42 +1111,25 @@ impl HttpFileParser {
             }
 
             if line.starts_with("//") || line.starts_with("#") {
+                query_comments.push(line.to_string());
                 self.current_line += 1;
                 continue;
             }
 
-            // Check if this looks like JSON (variables)
-            if line.starts_with('{') && query_lines.is_empty() == false {
-                in_variables = true;
-            }
-
             if in_variables {
-                // Try to parse as JSON
-                let mut var_lines = Vec::new();
-                var_lines.push(self.lines[self.current_line].clone());
-                
-                // Collect until we find the end or next request
-                self.current_line += 1;
-                while self.current_line < self.lines.len() {
-                    let next_line = &self.lines[self.current_line];
-                    if next_line.trim().starts_with("###") || 
-                       next_line.trim().starts_with("WEBSOCKET") || 
-                       next_line.trim().starts_with("GRAPHQL") ||
-                       next_line.trim().starts_with("RSOCKET") {
-                        break;
-                    }
-                    var_lines.push(next_line.clone());
-                    if next_line.trim().ends_with('}') {
-                        self.current_line += 1;
-                        break;
-                    }
-                    self.current_line += 1;
-                }
-
-                let var_str = var_lines.join("\n");
-                if let Ok(vars) = serde_json::from_str::<serde_json::Value>(&var_str) {
-                    variables = Some(vars);
+                if !line.is_empty() {
+                    let diagnostic = self.diagnostic(
+                        Severity::Warning,
+                        self.column(0),
+                        "Line after the GraphQL variables is ignored",
+                    );
+                    self.diagnostics.push(diagnostic);
                 }
+            } else if line.starts_with('{') && query_lines.iter().any(|l: &String| !l.trim().is_empty()) {
+                // JSON after the query holds the variables
+                in_variables = true;
+                variables = self.parse_graphql_variables();
+                continue;
             } else {
                 query_lines.push(self.lines[self.current_line].clone());
             }
@@ -528,14 +1137,84 @@ impl HttpFileParser {
             self.current_line += 1;
         }
 
-        let query = query_lines.join("\n");
+        let query = message_content(&query_lines).unwrap_or_default();
 
-        Ok(Some(GraphQLRequest {
+        Some(GraphQLRequest {
+            name: None,
+            tags: Vec::new(),
+            comments: Vec::new(),
+            span: Span::default(),
             uri,
             query,
+            query_comments,
             variables,
             headers,
-        }))
+            header_comments,
+        })
+    }
+}
+
+/// Joins the lines of a WebSocket or RSocket message or a GraphQL query
+/// without leading and trailing blank lines. `None` if all lines are blank.
+fn message_content(lines: &[String]) -> Option<String> {
+    let start = lines.iter().position(|l| !l.trim().is_empty())?;
+    let end = lines.iter().rposition(|l| !l.trim().is_empty())?;
+    Some(lines[start..=end].join("\n"))
+}
+
+/// Parses the tags of a `# @tag` directive, separated by whitespace or commas.
+fn parse_tags(argument: &str) -> Result<Vec<String>> {
+    let tags: Vec<String> = argument
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+    if tags.is_empty() {
+        anyhow::bail!("expected one or more tag names");
+    }
+    Ok(tags)
+}
+
+/// Parses a directive timeout: seconds, or a number with an `ms`, `s` or `m`
+/// unit such as `500 ms` or `2m`.
+fn parse_timeout(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("Expected a number of seconds, got '{}'", value))?;
+    match unit.trim() {
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "ms" => Ok(Duration::from_millis(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        unit => anyhow::bail!("Unknown timeout unit '{}'", unit),
+    }
+}
+
+/// Parses an in-file variable declaration: `@name = value`.
+fn parse_file_variable(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.strip_prefix('@')?.split_once('=')?;
+    let name = name.trim();
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    valid.then(|| (name.to_string(), value.trim().to_string()))
+}
+
+fn is_response_handler(line: &str) -> bool {
+    line.starts_with('>') && !line.starts_with(">>")
+}
+
+impl HttpFile {
+    /// The in-file variables seen by the request at `index`, with each name
+    /// at its last definition before or in that request.
+    pub fn variables_for(&self, index: usize) -> Vec<(String, String)> {
+        let mut variables: Vec<(String, String)> = Vec::new();
+        for variable in self.variables.iter().filter(|variable| variable.first_request <= index) {
+            variables.retain(|(name, _)| *name != variable.name);
+            variables.push((variable.name.clone(), variable.value.clone()));
+        }
+        variables
     }
 }
 
@@ -543,10 +1222,19 @@ pub fn parse_http_file(path: impl AsRef<Path>) -> Result<Vec<Request>> {
     let content = std::fs::read_to_string(path.as_ref())
         .with_context(|| format!("Failed to read file: {:?}", path.as_ref()))?;
     
-    let mut parser = HttpFileParser::new(content);
+    let mut parser = HttpFileParser::new(content).with_file(path.as_ref());
     parser.parse()
 }
 
+/// Like `parse_http_file`, but also returns the file's in-file variables.
+pub fn read_http_file(path: impl AsRef<Path>) -> Result<HttpFile> {
+    let content = std::fs::read_to_string(path.as_ref())
+        .with_context(|| format!("Failed to read file: {:?}", path.as_ref()))?;
+
+    let mut parser = HttpFileParser::new(content).with_file(path.as_ref());
+    parser.parse_file()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -568,7 +1256,7 @@ Accept: application/json
             assert_eq!(req.method, "GET");
             assert_eq!(req.uri, "https://api.example.com/users");
             assert_eq!(req.name, Some("Get Users".to_string()));
-            assert_eq!(req.headers.get("Accept"), Some(&"application/json".to_string()));
+            assert_eq!(req.headers.get("Accept"), Some("application/json"));
         }
     }
 
@@ -610,7 +1298,7 @@ Content-Type: application/json
             assert_eq!(req.method, "POST");
             assert_eq!(req.uri, "https://api.example.com/users");
             assert!(req.body.is_some());
-            assert!(req.body.as_ref().unwrap().contains("John Doe"));
+            assert!(req.body.as_ref().and_then(RequestBody::as_inline).unwrap().contains("John Doe"));
         }
     }
 
@@ -636,7 +1324,7 @@ Content-Type: application/json
         
         // Note: Multiple requests parsing may need improvement
         // For now, we'll test that at least one request is parsed
-        assert!(requests.len() >= 1);
+        assert!(!requests.is_empty());
         if let Request::Http(req) = &requests[0] {
             assert_eq!(req.method, "GET");
         }
@@ -744,8 +1432,8 @@ X-Custom: value
 
         assert_eq!(requests.len(), 1);
         if let Request::RSocket(rs) = &requests[0] {
-            assert_eq!(rs.headers.get("Content-Type"), Some(&"application/json".to_string()));
-            assert_eq!(rs.headers.get("X-Custom"), Some(&"value".to_string()));
+            assert_eq!(rs.headers.get("Content-Type"), Some("application/json"));
+            assert_eq!(rs.headers.get("X-Custom"), Some("value"));
             assert_eq!(rs.messages.len(), 1);
         }
     }
@@ -902,4 +1590,583 @@ GET https://api.example.com/users HTTP/2
             assert_eq!(req.http_version, Some("HTTP/2".to_string()));
         }
     }
+
+    #[test]
+    fn test_parse_unsupported_http_version() {
+        let content = "### Old\nGET https://api.example.com/HTTP/3 HTTP/1.0\n\n### New\nGET https://api.example.com  HTTP/3\n";
+        let mut parser = HttpFileParser::new(content.to_string());
+        assert!(parser.parse().is_err());
+        let found: Vec<String> = parser.diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            found,
+            ["line 5, column 30: error: Unsupported HTTP version: HTTP/3; expected HTTP/1.0, HTTP/1.1, HTTP/2 or HTTP/2 (Prior Knowledge)"]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_handler() {
+        let content = r###"
+### Create User
+POST https://api.example.com/users
+Content-Type: application/json
+
+{
+  "name": "John"
+}
+
+> {%
+    client.test("created", function() {
+        client.assert(response.status === 201, "Expected 201");
+    });
+%}
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        assert_eq!(requests.len(), 1);
+        if let Request::Http(req) = &requests[0] {
+            assert_eq!(req.body.as_ref().and_then(RequestBody::as_inline), Some("{\n  \"name\": \"John\"\n}"));
+            assert_eq!(req.response_handlers.len(), 1);
+            match &req.response_handlers[0] {
+                ScriptSource::Inline(script) => assert!(script.contains("client.test")),
+                other => panic!("expected inline script, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_content_after_response_handler_is_reported() {
+        let content = r###"### Create User
+POST https://api.example.com/users
+
+{"name": "John"}
+
+> {% client.log(response.status); %}
+{"name": "Jane"}
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        let Request::Http(req) = &requests[0] else { panic!("expected an HTTP request") };
+        assert_eq!(req.body.as_ref().and_then(RequestBody::as_inline), Some("{\"name\": \"John\"}"));
+        let found: Vec<String> = parser.diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            found,
+            ["line 7, column 1: warning: Line after the response handler is ignored; put the body before `>` handlers"]
+        );
+    }
+
+    #[test]
+    fn test_parse_output_files() {
+        let content = r###"
+### Download
+GET https://api.example.com/report
+Accept: application/pdf
+
+>> ./out/{{name}}.pdf
+
+### Upload and keep the receipt
+POST https://api.example.com/upload
+Content-Type: text/plain
+
+payload
+> {% client.log(response.status); %}
+>>! receipt.json
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        let Request::Http(download) = &requests[0] else { panic!("expected an HTTP request") };
+        assert_eq!(
+            download.output,
+            Some(OutputFile { path: "./out/{{name}}.pdf".to_string(), overwrite: false })
+        );
+        assert!(download.body.is_none());
+
+        let Request::Http(upload) = &requests[1] else { panic!("expected an HTTP request") };
+        assert_eq!(upload.output, Some(OutputFile { path: "receipt.json".to_string(), overwrite: true }));
+        assert_eq!(upload.body.as_ref().and_then(RequestBody::as_inline), Some("payload"));
+        assert_eq!(upload.response_handlers.len(), 1);
+    }
+
+    #[test]
+    fn test_output_redirect_inside_body_is_body_text() {
+        let content = r###"### Shell script
+POST https://api.example.com/scripts
+Content-Type: text/x-shellscript
+
+echo start
+>> not output either
+echo done
+
+>> result.txt
+leftover
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        let Request::Http(req) = &requests[0] else { panic!("expected an HTTP request") };
+        assert_eq!(
+            req.body.as_ref().and_then(RequestBody::as_inline),
+            Some("echo start\n>> not output either\necho done")
+        );
+        assert_eq!(req.output, Some(OutputFile { path: "result.txt".to_string(), overwrite: false }));
+        let found: Vec<String> = parser.diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(found, ["line 10, column 1: warning: Line after the output file is ignored; put the body before `>>`"]);
+    }
+
+    #[test]
+    fn test_parse_response_handler_without_body() {
+        let content = r###"
+### Get Users
+GET https://api.example.com/users
+Accept: application/json
+> {% client.global.set("count", response.body.length); %}
+
+### Next
+GET https://api.example.com/next
+> ./handlers/check.js
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        assert_eq!(requests.len(), 2);
+        if let Request::Http(req) = &requests[0] {
+            assert!(req.body.is_none());
+            assert_eq!(req.headers.len(), 1);
+            assert_eq!(
+                req.response_handlers,
+                vec![ScriptSource::Inline("client.global.set(\"count\", response.body.length);".to_string())]
+            );
+        }
+        if let Request::Http(req) = &requests[1] {
+            assert_eq!(req.response_handlers, vec![ScriptSource::File("./handlers/check.js".to_string())]);
+        }
+    }
+
+    #[test]
+    fn test_parse_pre_request_scripts() {
+        let content = r###"
+### Signed Request
+< {% request.variables.set("ts", Date.now()) %}
+< ./scripts/sign.js
+POST https://api.example.com/orders?ts={{ts}}
+Content-Type: application/json
+
+{"id": 1}
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        assert_eq!(requests.len(), 1);
+        if let Request::Http(req) = &requests[0] {
+            assert_eq!(req.name, Some("Signed Request".to_string()));
+            assert_eq!(req.method, "POST");
+            assert_eq!(
+                req.pre_request_scripts,
+                vec![
+                    ScriptSource::Inline("request.variables.set(\"ts\", Date.now())".to_string()),
+                    ScriptSource::File("./scripts/sign.js".to_string()),
+                ]
+            );
+            assert_eq!(req.body.as_ref().and_then(RequestBody::as_inline), Some("{\"id\": 1}"));
+        }
+    }
+
+    #[test]
+    fn test_parse_file_bodies() {
+        let content = r###"
+### Upload
+POST https://api.example.com/upload
+Content-Type: application/octet-stream
+
+< ./data/payload.bin
+
+### Templated
+POST https://api.example.com/items
+Content-Type: application/json
+
+<@ ./payload.json
+> {% client.test("ok", function () {}); %}
+
+### Inline XML
+POST https://api.example.com/xml
+Content-Type: application/xml
+
+<root/>
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+        let bodies: Vec<_> = requests
+            .iter()
+            .map(|r| match r {
+                Request::Http(req) => req.body.clone(),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            bodies,
+            vec![
+                Some(RequestBody::File("./data/payload.bin".to_string())),
+                Some(RequestBody::TemplateFile("./payload.json".to_string())),
+                Some(RequestBody::Inline("<root/>".to_string())),
+            ]
+        );
+        assert_eq!(bodies[1].as_ref().unwrap().to_string(), "<@ ./payload.json");
+    }
+
+    #[test]
+    fn test_parse_bare_separator_before_named_request() {
+        let content = r###"
+###
+
+### Create User
+POST https://api.example.com/users
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        assert_eq!(requests.len(), 1);
+        if let Request::Http(req) = &requests[0] {
+            assert_eq!(req.name, Some("Create User".to_string()));
+            assert_eq!(req.method, "POST");
+        }
+    }
+
+    #[test]
+    fn test_parse_named_requests_of_each_kind() {
+        let content = r###"
+### Users
+GET https://api.example.com/users
+### Chat
+WEBSOCKET ws://localhost:8080/ws
+### Query
+GRAPHQL http://localhost:8080/graphql
+
+{ users { name } }
+### Stream
+RSOCKET ws://localhost:7878/rsocket
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        assert_eq!(requests.len(), 4);
+        let Request::Http(http) = &requests[0] else { panic!("expected an HTTP request") };
+        assert_eq!(http.name.as_deref(), Some("Users"));
+        assert!(http.headers.is_empty());
+        let Request::WebSocket(ws) = &requests[1] else { panic!("expected a WebSocket request") };
+        assert_eq!(ws.name.as_deref(), Some("Chat"));
+        let Request::GraphQL(gql) = &requests[2] else { panic!("expected a GraphQL request") };
+        assert_eq!(gql.name.as_deref(), Some("Query"));
+        assert_eq!(gql.query, "{ users { name } }");
+        let Request::RSocket(rs) = &requests[3] else { panic!("expected an RSocket request") };
+        assert_eq!(rs.name.as_deref(), Some("Stream"));
+    }
+
+    #[test]
+    fn test_parse_assertions() {
+        let content = r###"
+### Create User
+# @assert status == 201
+POST https://api.example.com/users
+Content-Type: application/json
+// @assert jsonpath $.id exists
+
+{"name": "John"}
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        assert_eq!(requests.len(), 1);
+        if let Request::Http(req) = &requests[0] {
+            assert_eq!(req.assertions.len(), 2);
+            assert_eq!(req.assertions[0].text, "status == 201");
+            assert_eq!(req.assertions[1].text, "jsonpath $.id exists");
+            assert!(req.comments.is_empty());
+            assert_eq!(req.headers.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_parse_no_cookie_jar() {
+        let content = r###"
+### Anonymous
+# @no-cookie-jar
+GET https://api.example.com/users
+
+### With Cookies
+GET https://api.example.com/users
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        let options: Vec<bool> = requests
+            .iter()
+            .map(|r| match r {
+                Request::Http(req) => req.options.no_cookie_jar,
+                _ => panic!("expected an HTTP request"),
+            })
+            .collect();
+        assert_eq!(options, vec![true, false]);
+    }
+
+    #[test]
+    fn test_parse_repeated_headers() {
+        let content = r###"
+### Feed
+GET https://api.example.com/feed
+Accept: application/atom+xml
+X-Request-ID: 7
+accept: application/xml
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        let Request::Http(req) = &requests[0] else { panic!("expected an HTTP request") };
+        assert_eq!(
+            req.headers.iter().collect::<Vec<_>>(),
+            [("Accept", "application/atom+xml"), ("X-Request-ID", "7"), ("accept", "application/xml")]
+        );
+        assert_eq!(req.headers.get("x-request-id"), Some("7"));
+    }
+
+    #[test]
+    fn test_parse_request_options() {
+        let content = r###"
+### Slow Upload
+# @no-redirect
+# @timeout 600
+# @connection-timeout 2 m
+// @no-log
+POST https://api.example.com/upload
+
+### Fast
+# @timeout 500ms
+GET https://api.example.com/health
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let requests = parser.parse().unwrap();
+
+        let Request::Http(upload) = &requests[0] else { panic!("expected an HTTP request") };
+        assert_eq!(
+            upload.options,
+            RequestOptions {
+                no_cookie_jar: false,
+                no_redirect: true,
+                max_redirects: None,
+                timeout: Some(Duration::from_secs(600)),
+                connection_timeout: Some(Duration::from_secs(120)),
+                no_log: true,
+            }
+        );
+        assert!(upload.comments.is_empty());
+
+        let Request::Http(health) = &requests[1] else { panic!("expected an HTTP request") };
+        assert_eq!(health.options.timeout, Some(Duration::from_millis(500)));
+        assert!(!health.options.no_redirect);
+    }
+
+    #[test]
+    fn test_parse_invalid_timeout() {
+        for directive in ["# @timeout soon", "# @connection-timeout 5 h"] {
+            let content = format!("### Broken\n{}\nGET https://api.example.com\n", directive);
+            let err = HttpFileParser::new(content).parse().unwrap_err();
+            assert!(err.to_string().contains("line 2"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let content = r###"
+### Health
+# @tag smoke, ops
+// @tag slow
+GET https://api.example.com/health
+### Events
+# @tag smoke
+WEBSOCKET ws://localhost:8080/events
+"###.to_string();
+
+        let requests = HttpFileParser::new(content).parse().unwrap();
+        assert_eq!(requests[0].tags(), ["smoke", "ops", "slow"]);
+        assert_eq!(requests[1].tags(), ["smoke"]);
+
+        let err = HttpFileParser::new("###\n# @tag\nGET https://api.example.com\n".to_string())
+            .parse()
+            .unwrap_err();
+        assert!(err.to_string().contains("Invalid @tag"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_invalid_assertion() {
+        let content = r###"
+### Broken
+# @assert status =~ 201
+GET https://api.example.com/users
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let err = parser.parse().unwrap_err();
+        assert!(err.to_string().contains("line 3"));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let content = r###"GET https://example.com/outside
+
+### Users
+# @retries 3
+GET https://api.example.com/users
+  Authorization Bearer abc
+:empty
+
+### No URL
+POST
+
+GRAPHQL https://api.example.com/graphql
+
+query ($id: ID!) { user(id: $id) { name } }
+
+{
+  "id": 1,
+}
+
+WEBSOCKET
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content).with_file("api.http");
+        let err = parser.parse().unwrap_err();
+        let found: Vec<(usize, usize, Severity, &str)> = parser
+            .diagnostics()
+            .iter()
+            .map(|d| (d.line, d.column, d.severity, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (1, 1, Severity::Warning, "Line is outside of a request and is ignored; start requests with `###`"),
+                (4, 3, Severity::Warning, "Unknown directive @retries is ignored"),
+                (6, 3, Severity::Error, "Expected a header `Name: value`"),
+                (7, 1, Severity::Error, "Header name is empty"),
+                (10, 5, Severity::Error, "Expected a URL after POST"),
+                (18, 1, Severity::Error, "Invalid GraphQL variables: trailing comma"),
+                (20, 10, Severity::Error, "Expected a URL after WEBSOCKET"),
+            ]
+        );
+        assert!(err.to_string().starts_with("api.http:6:3: error: Expected a header"), "{}", err);
+        assert!(!err.to_string().contains("warning"));
+    }
+
+    #[test]
+    fn test_unterminated_script() {
+        let content = "### Login\nPOST https://api.example.com/login\n\n> {%\n  client.log(1);\n\n### Users\nGET https://api.example.com/users\n".to_string();
+        let mut parser = HttpFileParser::new(content).with_file("api.http");
+        let err = parser.parse().unwrap_err();
+        assert_eq!(err.to_string(), "api.http:4:3: error: Script is not closed with `%}`");
+    }
+
+    #[test]
+    fn test_warnings_do_not_fail_parsing() {
+        let content = "### Users\n# @retries 3\nGET https://api.example.com/users\n".to_string();
+        let mut parser = HttpFileParser::new(content);
+        let file = parser.parse_file().unwrap();
+        assert_eq!(file.requests.len(), 1);
+        assert_eq!(file.diagnostics.len(), 1);
+        assert_eq!(file.diagnostics[0].to_string(), "line 2, column 3: warning: Unknown directive @retries is ignored");
+    }
+
+    #[test]
+    fn test_parse_file_variables() {
+        let content = r###"
+@host = https://api.example.com
+@base={{host}}/v1
+
+### Get Users
+@limit = 10
+GET {{base}}/users?limit={{limit}}
+
+### Not a variable
+POST {{base}}/users
+Content-Type: application/json
+
+{"email": "@me = you"}
+"###.to_string();
+
+        let mut parser = HttpFileParser::new(content);
+        let file = parser.parse_file().unwrap();
+
+        assert_eq!(file.requests.len(), 2);
+        let variables: Vec<(&str, &str, usize)> = file
+            .variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.value.as_str(), v.first_request))
+            .collect();
+        assert_eq!(
+            variables,
+            [("host", "https://api.example.com", 0), ("base", "{{host}}/v1", 0), ("limit", "10", 0)]
+        );
+        if let Request::Http(req) = &file.requests[0] {
+            assert_eq!(req.name, Some("Get Users".to_string()));
+            assert_eq!(req.uri, "{{base}}/users?limit={{limit}}");
+        }
+    }
+
+    #[test]
+    fn test_file_variables_are_scoped() {
+        let content = r###"
+@host = https://one.example.com
+@limit = 10
+
+### First
+GET {{host}}/users
+
+### Second
+@host = https://two.example.com
+GET {{host}}/users
+
+###
+GET {{host}}/orders
+"###.to_string();
+
+        let file = HttpFileParser::new(content).parse_file().unwrap();
+        let host = |index: usize| {
+            file.variables_for(index)
+                .into_iter()
+                .find(|(name, _)| name == "host")
+                .map(|(_, value)| value)
+        };
+
+        assert_eq!(host(0).as_deref(), Some("https://one.example.com"));
+        assert_eq!(host(1).as_deref(), Some("https://two.example.com"));
+        assert_eq!(host(2).as_deref(), Some("https://two.example.com"));
+        assert_eq!(
+            file.variables_for(0),
+            [
+                ("host".to_string(), "https://one.example.com".to_string()),
+                ("limit".to_string(), "10".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_file_variable_rejects_invalid_names() {
+        assert_eq!(parse_file_variable("@ = x"), None);
+        assert_eq!(parse_file_variable("@my var = x"), None);
+        assert_eq!(parse_file_variable("@token"), None);
+        assert_eq!(parse_file_variable("@empty ="), Some(("empty".to_string(), String::new())));
+    }
 }
@@ -0,0 +1,89 @@
+//! Reports written after a run, chosen with `--report <format>:<path>`.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+
+use crate::{html, junit};
+use crate::summary::RunSummary;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Report {
+    /// `junit:<path>`: a JUnit XML file
+    Junit(PathBuf),
+    /// `html:<dir>`: a self-contained `index.html` in the directory
+    Html(PathBuf),
+}
+
+impl Report {
+    /// Writes the report for the files that were run, creating missing directories.
+    pub fn write(&self, summaries: &[RunSummary]) -> Result<()> {
+        match self {
+            Report::Junit(path) => {
+                if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                    create_dir(parent)?;
+                }
+                std::fs::write(path, junit::render(summaries))
+                    .with_context(|| format!("Failed to write JUnit report: {:?}", path))
+            }
+            Report::Html(dir) => {
+                create_dir(dir)?;
+                let path = dir.join("index.html");
+                std::fs::write(&path, html::render(summaries, Utc::now()))
+                    .with_context(|| format!("Failed to write HTML report: {:?}", path))
+            }
+        }
+    }
+}
+
+fn create_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create directory: {:?}", dir))
+}
+
+impl FromStr for Report {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (format, path) = value
+            .split_once(':')
+            .filter(|(_, path)| !path.is_empty())
+            .with_context(|| format!("expected <format>:<path> such as junit:report.xml, got `{}`", value))?;
+        match format {
+            "junit" => Ok(Report::Junit(PathBuf::from(path))),
+            "html" => Ok(Report::Html(PathBuf::from(path))),
+            _ => anyhow::bail!("unknown report format `{}`, expected junit or html", format),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("junit:out/report.xml".parse::<Report>().unwrap(), Report::Junit(PathBuf::from("out/report.xml")));
+        assert_eq!("junit:C:/reports/a.xml".parse::<Report>().unwrap(), Report::Junit(PathBuf::from("C:/reports/a.xml")));
+        assert_eq!("html:out/report".parse::<Report>().unwrap(), Report::Html(PathBuf::from("out/report")));
+        assert!("junit:".parse::<Report>().is_err());
+        assert!("report.xml".parse::<Report>().is_err());
+        let err = "xml:report.xml".parse::<Report>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown report format `xml`, expected junit or html");
+    }
+
+    #[test]
+    fn test_write_creates_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reports/junit.xml");
+        Report::Junit(path.clone()).write(&[RunSummary::new("api.http")]).unwrap();
+        let xml = std::fs::read_to_string(path).unwrap();
+        assert!(xml.contains("<testsuite name=\"api.http\" tests=\"0\""));
+
+        let dir = dir.path().join("reports/html");
+        Report::Html(dir.clone()).write(&[RunSummary::new("api.http")]).unwrap();
+        let html = std::fs::read_to_string(dir.join("index.html")).unwrap();
+        assert!(html.contains("<h2>api.http</h2>"));
+    }
+}
@@ -2,9 +2,17 @@
 //! Uses WebSocket transport (ws://, wss://) for cross-platform support.
 
 use anyhow::{Context, Result};
+use bytes::{BufMut, BytesMut};
+use futures_util::{future, SinkExt, StreamExt};
+use rsocket_rust::error::RSocketError;
+use rsocket_rust::frame::Frame;
 use rsocket_rust::prelude::*;
-use rsocket_rust::utils::EchoRSocket;
+use rsocket_rust::transport::{Connection, FrameSink, FrameStream};
+use rsocket_rust::utils::{EchoRSocket, Writeable};
 use rsocket_rust_transport_websocket::WebsocketClientTransport;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async_tls_with_config, Connector, MaybeTlsStream, WebSocketStream};
 
 use crate::env::EnvironmentManager;
 use crate::parser::RSocketRequest;
@@ -15,10 +23,10 @@ pub(crate) fn uri_to_transport_addr(uri: &str) -> Result<String> {
     let s = uri.trim();
     if s.starts_with("ws://") || s.starts_with("wss://") {
         Ok(s.to_string())
-    } else if s.starts_with("rs://") {
-        Ok(format!("ws://{}", &s["rs://".len()..]))
-    } else if s.starts_with("tcp://") {
-        Ok(format!("ws://{}", &s["tcp://".len()..]))
+    } else if let Some(rest) = s.strip_prefix("rs://") {
+        Ok(format!("ws://{}", rest))
+    } else if let Some(rest) = s.strip_prefix("tcp://") {
+        Ok(format!("ws://{}", rest))
     } else if s.contains("://") {
         anyhow::bail!("RSocket expects ws://, wss://, rs://, or tcp:// scheme");
     } else {
@@ -26,13 +34,65 @@ pub(crate) fn uri_to_transport_addr(uri: &str) -> Result<String> {
     }
 }
 
+/// WebSocket transport with a custom TLS connector, which
+/// `WebsocketClientTransport` doesn't support.
+struct TlsWebsocketTransport {
+    addr: String,
+    connector: native_tls::TlsConnector,
+}
+
+#[rsocket_rust::async_trait]
+impl Transport for TlsWebsocketTransport {
+    type Conn = TlsWebsocketConnection;
+
+    async fn connect(self) -> rsocket_rust::Result<TlsWebsocketConnection> {
+        let connector = Some(Connector::NativeTls(self.connector));
+        let (stream, _) = connect_async_tls_with_config(self.addr.as_str(), None, false, connector)
+            .await
+            .map_err(|e| RSocketError::Other(e.into()))?;
+        Ok(TlsWebsocketConnection(stream))
+    }
+}
+
+struct TlsWebsocketConnection(WebSocketStream<MaybeTlsStream<TcpStream>>);
+
+impl Connection for TlsWebsocketConnection {
+    fn split(self) -> (Box<FrameSink>, Box<FrameStream>) {
+        let (sink, stream) = self.0.split();
+        let sink = sink
+            .with(|frame: Frame| {
+                let mut buf = BytesMut::new();
+                frame.write_to(&mut buf);
+                future::ready(Ok(Message::binary(buf.to_vec())))
+            })
+            .sink_map_err(|e: tokio_tungstenite::tungstenite::Error| RSocketError::Other(e.into()));
+        let stream = stream.map(|message| {
+            let mut buf = BytesMut::new();
+            buf.put_slice(&message.map_err(|e| RSocketError::Other(e.into()))?.into_data());
+            Frame::decode(&mut buf).map_err(RSocketError::Other)
+        });
+        (Box::new(sink), Box::new(stream))
+    }
+}
+
 pub struct RSocketClient {
     env_manager: EnvironmentManager,
+    tls_connector: Option<native_tls::TlsConnector>,
 }
 
 impl RSocketClient {
     pub fn new(env_manager: EnvironmentManager) -> Self {
-        Self { env_manager }
+        Self {
+            env_manager,
+            tls_connector: None,
+        }
+    }
+
+    /// Uses `connector` for `wss://` connections, e.g. one built by
+    /// `HttpClientConfig::build_tls_connector` with a client certificate.
+    pub fn with_tls_connector(mut self, connector: native_tls::TlsConnector) -> Self {
+        self.tls_connector = Some(connector);
+        self
     }
 
     pub async fn execute_request(
@@ -47,12 +107,26 @@ impl RSocketClient {
 
         println!("Connecting to RSocket: {} ({})", uri, addr);
 
-        let client = RSocketFactory::connect()
-            .transport(WebsocketClientTransport::from(addr.as_str()))
-            .acceptor(Box::new(|| Box::new(EchoRSocket)))
-            .start()
-            .await
-            .context("Failed to connect to RSocket")?;
+        let client = match &self.tls_connector {
+            Some(connector) if addr.starts_with("wss://") => {
+                RSocketFactory::connect()
+                    .transport(TlsWebsocketTransport {
+                        addr: addr.clone(),
+                        connector: connector.clone(),
+                    })
+                    .acceptor(Box::new(|| Box::new(EchoRSocket)))
+                    .start()
+                    .await
+            }
+            _ => {
+                RSocketFactory::connect()
+                    .transport(WebsocketClientTransport::from(addr.as_str()))
+                    .acceptor(Box::new(|| Box::new(EchoRSocket)))
+                    .start()
+                    .await
+            }
+        }
+        .context("Failed to connect to RSocket")?;
 
         for message in &request.messages {
             for _ in 0..message.wait_for_server {
@@ -0,0 +1,433 @@
+//! JavaScript pre-request (`< {% ... %}`) and response handler (`> {% ... %}`) scripts.
+//! Scripts run in an embedded Boa engine against the IntelliJ `client`,
+//! `request` and `response` objects; `client.global` is backed by the
+//! environment manager.
+
+use anyhow::{Context as _, Result};
+use boa_engine::vm::RuntimeLimits;
+use boa_engine::{Context, Source};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::client::HttpResponse;
+use crate::env::EnvironmentManager;
+use crate::parser::{HttpRequest, RequestBody, ScriptSource};
+
+/// Iterations a single loop may run before the script fails, so that a
+/// runaway `while (true) {}` does not hang the run.
+const LOOP_ITERATION_LIMIT: u64 = 1_000_000;
+/// Nested calls a script may make before it fails.
+const RECURSION_LIMIT: usize = 512;
+/// Values the engine's stack may hold before the script fails.
+const STACK_SIZE_LIMIT: usize = 10 * 1024;
+
+/// Definitions of the `client` object and helpers shared by every script.
+const PRELUDE: &str = r#"
+function __Exit() {}
+function __message(e) {
+  if (e instanceof Error) { return e.message; }
+  return String(e);
+}
+function __text(v) {
+  if (typeof v === "string") { return v; }
+  try { return JSON.stringify(v); } catch (e) { return String(v); }
+}
+var __out = { tests: [], logs: [], globals: __globals, variables: {}, error: null };
+var client = {
+  global: {
+    set: function (name, value) { __out.globals[String(name)] = __text(value); },
+    get: function (name) {
+      return Object.prototype.hasOwnProperty.call(__out.globals, name) ? __out.globals[name] : null;
+    },
+    isEmpty: function () { return Object.keys(__out.globals).length === 0; },
+    clear: function (name) { delete __out.globals[name]; },
+    clearAll: function () { __out.globals = {}; }
+  },
+  log: function () { __out.logs.push(Array.prototype.map.call(arguments, __text).join(" ")); },
+  assert: function (condition, message) {
+    if (!condition) { throw new Error(message || "Assertion failed"); }
+  },
+  test: function (name, fn) {
+    try {
+      fn();
+      __out.tests.push({ name: String(name), passed: true });
+    } catch (e) {
+      if (e instanceof __Exit) { throw e; }
+      __out.tests.push({ name: String(name), passed: false, message: __message(e) });
+    }
+  },
+  exit: function () { throw new __Exit(); }
+};
+function __request(raw) {
+  return {
+    method: raw.method,
+    url: { getRaw: function () { return raw.url; } },
+    body: { getRaw: function () { return raw.body; } },
+    variables: {
+      set: function (name, value) { __out.variables[String(name)] = __text(value); },
+      get: function (name) {
+        return Object.prototype.hasOwnProperty.call(__out.variables, name) ? __out.variables[name] : null;
+      }
+    },
+    environment: {
+      get: function (name) {
+        return Object.prototype.hasOwnProperty.call(raw.environment, name) ? raw.environment[name] : null;
+      }
+    }
+  };
+}
+function __response(raw) {
+  return {
+    status: raw.status,
+    body: raw.body,
+    contentType: raw.contentType,
+    headers: {
+      valuesOf: function (name) {
+        var wanted = String(name).toLowerCase();
+        return raw.headers
+          .filter(function (h) { return h[0].toLowerCase() === wanted; })
+          .map(function (h) { return h[1]; });
+      },
+      valueOf: function (name) {
+        var values = this.valuesOf(name);
+        return values.length > 0 ? values[0] : null;
+      }
+    }
+  };
+}
+"#;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScriptOutput {
+    pub tests: Vec<TestResult>,
+    pub logs: Vec<String>,
+    /// Request-scoped variables set with `request.variables.set()`.
+    pub variables: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct RawOutput {
+    tests: Vec<TestResult>,
+    logs: Vec<String>,
+    globals: HashMap<String, String>,
+    #[serde(default)]
+    variables: HashMap<String, String>,
+    error: Option<String>,
+}
+
+/// Reads the source of a script, resolving file references against `base_path`.
+pub fn load_script(source: &ScriptSource, base_path: &Path) -> Result<String> {
+    match source {
+        ScriptSource::Inline(code) => Ok(code.clone()),
+        ScriptSource::File(path) => {
+            let path = base_path.join(path);
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read script: {:?}", path))
+        }
+    }
+}
+
+/// Runs a pre-request script for `request`. Unlike response handlers, an
+/// uncaught error aborts the request.
+pub fn run_pre_request_script(
+    script: &str,
+    request: &HttpRequest,
+    env_name: &str,
+    env_manager: &EnvironmentManager,
+) -> Result<ScriptOutput> {
+    let environment = env_manager
+        .get_environment(env_name)
+        .map(|env| env.variables.clone())
+        .unwrap_or_default();
+    let raw_request = serde_json::json!({
+        "method": request.method,
+        "url": request.uri,
+        "body": request.body.as_ref().and_then(RequestBody::as_inline),
+        "environment": environment,
+    });
+    let bindings = format!("var request = __request({});", serde_json::to_string(&raw_request)?);
+    let raw = evaluate(&bindings, script, env_manager)?;
+
+    if let Some(error) = raw.error {
+        anyhow::bail!("Pre-request script failed: {}", error);
+    }
+
+    Ok(ScriptOutput {
+        tests: raw.tests,
+        logs: raw.logs,
+        variables: raw.variables,
+    })
+}
+
+/// Runs a response handler against `response`. Uncaught errors are reported
+/// as a failed test so that they count against the run.
+pub fn run_response_handler(
+    script: &str,
+    response: &HttpResponse,
+    env_manager: &EnvironmentManager,
+) -> Result<ScriptOutput> {
+    let bindings = format!(
+        "var response = __response({});",
+        serde_json::to_string(&response_to_json(response))?
+    );
+    let raw = evaluate(&bindings, script, env_manager)?;
+
+    let mut tests = raw.tests;
+    if let Some(error) = raw.error {
+        tests.push(TestResult {
+            name: "Response handler".to_string(),
+            passed: false,
+            message: Some(error),
+        });
+    }
+
+    Ok(ScriptOutput {
+        tests,
+        logs: raw.logs,
+        variables: raw.variables,
+    })
+}
+
+fn evaluate(bindings: &str, script: &str, env_manager: &EnvironmentManager) -> Result<RawOutput> {
+    let source = format!(
+        "var __globals = {};\n{}\n{}\ntry {{\n{}\n}} catch (e) {{ if (!(e instanceof __Exit)) {{ __out.error = __message(e); }} }}\nJSON.stringify(__out)",
+        serde_json::to_string(&env_manager.globals())?,
+        PRELUDE,
+        bindings,
+        script
+    );
+
+    let mut context = Context::default();
+    let mut limits = RuntimeLimits::default();
+    limits.set_loop_iteration_limit(LOOP_ITERATION_LIMIT);
+    limits.set_recursion_limit(RECURSION_LIMIT);
+    limits.set_stack_size_limit(STACK_SIZE_LIMIT);
+    context.set_runtime_limits(limits);
+    let raw = match context.eval(Source::from_bytes(&source)) {
+        Ok(value) => {
+            let json = value
+                .to_string(&mut context)
+                .map_err(|e| anyhow::anyhow!("Failed to read script result: {}", e))?
+                .to_std_string_escaped();
+            serde_json::from_str::<RawOutput>(&json).context("Failed to decode script result")?
+        }
+        // Syntax errors surface here, before any of the script has run, and
+        // exceeded runtime limits, which scripts cannot catch
+        Err(e) => RawOutput {
+            tests: Vec::new(),
+            logs: Vec::new(),
+            globals: env_manager.globals(),
+            variables: HashMap::new(),
+            error: Some(e.to_string()),
+        },
+    };
+
+    env_manager.replace_globals(raw.globals.clone());
+    Ok(raw)
+}
+
+fn response_to_json(response: &HttpResponse) -> serde_json::Value {
+    let headers: Vec<[&str; 2]> = response.headers.iter().map(|(k, v)| [k, v]).collect();
+
+    let mut content_type = response.content_type.as_deref().unwrap_or("").split(';');
+    let mime_type = content_type.next().unwrap_or("").trim().to_string();
+    let charset = content_type
+        .filter_map(|p| p.trim().strip_prefix("charset="))
+        .next()
+        .map(|c| c.trim_matches('"').to_string());
+
+    serde_json::json!({
+        "status": response.status,
+        "headers": headers,
+        "body": parse_body(response),
+        "contentType": { "mimeType": mime_type, "charset": charset },
+    })
+}
+
+/// The body is exposed as parsed JSON when it is JSON, as a string otherwise.
+fn parse_body(response: &HttpResponse) -> serde_json::Value {
+    let looks_like_json = response
+        .content_type
+        .as_deref()
+        .is_some_and(|ct| ct.contains("json"))
+        || response.body.text().trim_start().starts_with(['{', '[']);
+
+    if looks_like_json {
+        if let Ok(json) = serde_json::from_str(response.body.text()) {
+            return json;
+        }
+    }
+    serde_json::Value::String(response.body.text().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::ResponseBody;
+    use crate::headers::Headers;
+    use crate::timing::Timings;
+    use crate::parser::{RequestOptions, Span};
+
+    fn json_response(body: &str) -> HttpResponse {
+        HttpResponse {
+            request: Default::default(),
+            status: 200,
+            version: reqwest::Version::HTTP_11,
+            redirects: Vec::new(),
+            saved_to: None,
+            timings: Timings::default(),
+            headers: Headers::from([("Content-Type".to_string(), "application/json; charset=utf-8".to_string())]),
+            body: ResponseBody::from(body),
+            content_type: Some("application/json; charset=utf-8".to_string()),
+            duration: std::time::Duration::ZERO,
+            tests: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_client_test_pass_and_fail() {
+        let env_manager = EnvironmentManager::new(".");
+        let script = r#"
+            client.test("status is 200", function () {
+                client.assert(response.status === 200, "Unexpected status");
+            });
+            client.test("name matches", function () {
+                client.assert(response.body.name === "Jane", "Wrong name");
+            });
+        "#;
+        let output = run_response_handler(script, &json_response(r#"{"name": "John"}"#), &env_manager).unwrap();
+
+        assert_eq!(output.tests.len(), 2);
+        assert!(output.tests[0].passed);
+        assert!(!output.tests[1].passed);
+        assert_eq!(output.tests[1].message.as_deref(), Some("Wrong name"));
+    }
+
+    #[test]
+    fn test_global_set_persists() {
+        let env_manager = EnvironmentManager::new(".");
+        let script = r#"client.global.set("token", response.body.token);"#;
+        run_response_handler(script, &json_response(r#"{"token": "abc123"}"#), &env_manager).unwrap();
+
+        assert_eq!(env_manager.get_global("token"), Some("abc123".to_string()));
+    }
+
+    #[test]
+    fn test_headers_and_content_type() {
+        let env_manager = EnvironmentManager::new(".");
+        let script = r#"
+            client.log(response.headers.valueOf("content-type"));
+            client.log(response.contentType.mimeType, response.contentType.charset);
+        "#;
+        let output = run_response_handler(script, &json_response("{}"), &env_manager).unwrap();
+
+        assert_eq!(output.logs, vec!["application/json; charset=utf-8", "application/json utf-8"]);
+    }
+
+    #[test]
+    fn test_exit_stops_script() {
+        let env_manager = EnvironmentManager::new(".");
+        let script = r#"client.log("before"); client.exit(); client.log("after");"#;
+        let output = run_response_handler(script, &json_response("{}"), &env_manager).unwrap();
+
+        assert_eq!(output.logs, vec!["before"]);
+        assert!(output.tests.is_empty());
+    }
+
+    #[test]
+    fn test_uncaught_error_reported_as_failure() {
+        let env_manager = EnvironmentManager::new(".");
+        let output = run_response_handler("client.assert(false, 'boom');", &json_response("{}"), &env_manager).unwrap();
+
+        assert_eq!(output.tests.len(), 1);
+        assert!(!output.tests[0].passed);
+        assert_eq!(output.tests[0].message.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_syntax_error_reported_as_failure() {
+        let env_manager = EnvironmentManager::new(".");
+        let output = run_response_handler("client.log(", &json_response("{}"), &env_manager).unwrap();
+
+        assert_eq!(output.tests.len(), 1);
+        assert!(!output.tests[0].passed);
+    }
+
+    #[test]
+    fn test_runaway_script_reported_as_failure() {
+        let env_manager = EnvironmentManager::new(".");
+        for script in ["while (true) {}", "function f() { return f(); } f();"] {
+            let output = run_response_handler(script, &json_response("{}"), &env_manager).unwrap();
+
+            assert_eq!(output.tests.len(), 1, "{}", script);
+            assert!(!output.tests[0].passed, "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_plain_text_body() {
+        let env_manager = EnvironmentManager::new(".");
+        let mut response = json_response("hello");
+        response.content_type = Some("text/plain".to_string());
+        let output = run_response_handler("client.log(typeof response.body, response.body);", &response, &env_manager).unwrap();
+
+        assert_eq!(output.logs, vec!["string hello"]);
+    }
+
+    fn request(uri: &str) -> HttpRequest {
+        HttpRequest {
+            name: None,
+            method: "GET".to_string(),
+            uri: uri.to_string(),
+            http_version: None,
+            headers: Headers::new(),
+            body: None,
+            comments: Vec::new(),
+            header_comments: Vec::new(),
+            pre_request_scripts: Vec::new(),
+            response_handlers: Vec::new(),
+            assertions: Vec::new(),
+            options: RequestOptions::default(),
+            output: None,
+            tags: Vec::new(),
+            span: Span::default(),
+        }
+    }
+
+    #[test]
+    fn test_pre_request_sets_variables() {
+        let env_manager = EnvironmentManager::new(".");
+        let script = r#"
+            request.variables.set("ts", 1700000000000);
+            request.variables.set("path", request.url.getRaw().split("/").pop());
+        "#;
+        let output = run_pre_request_script(script, &request("https://example.com/users"), "dev", &env_manager).unwrap();
+
+        assert_eq!(output.variables.get("ts"), Some(&"1700000000000".to_string()));
+        assert_eq!(output.variables.get("path"), Some(&"users".to_string()));
+    }
+
+    #[test]
+    fn test_pre_request_error_aborts() {
+        let env_manager = EnvironmentManager::new(".");
+        let result = run_pre_request_script("throw new Error('no key');", &request("https://example.com"), "dev", &env_manager);
+
+        assert!(result.unwrap_err().to_string().contains("no key"));
+    }
+
+    #[test]
+    fn test_load_inline_script() {
+        let source = ScriptSource::Inline("client.log(1);".to_string());
+        assert_eq!(load_script(&source, Path::new(".")).unwrap(), "client.log(1);");
+    }
+}
@@ -0,0 +1,211 @@
+//! Picks the requests of a file to run, by name, line, pattern or tag.
+
+use regex::Regex;
+
+use crate::parser::Request;
+
+/// Criteria given on the command line. A request is selected when it meets
+/// all of the criteria that are set; with none set every request is selected.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// The request with exactly this name
+    pub name: Option<String>,
+    /// The request containing this 1-based line, or the one above it when the
+    /// line is between two requests
+    pub line: Option<usize>,
+    /// Requests whose name or URI matches the pattern anywhere
+    pub filter: Option<Regex>,
+    /// Requests with at least one of these tags
+    pub tags: Vec<String>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.line.is_none() && self.filter.is_none() && self.tags.is_empty()
+    }
+
+    /// The selected requests, in file order.
+    pub fn select<'a>(&self, requests: &'a [Request]) -> Vec<&'a Request> {
+        let at_line = self.line.and_then(|line| {
+            requests
+                .iter()
+                .rposition(|request| request.span().start_line <= line)
+        });
+        requests
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.line.is_none() || at_line == Some(*index))
+            .map(|(_, request)| request)
+            .filter(|request| self.matches(request))
+            .collect()
+    }
+
+    /// Whether `request` meets the name, filter and tag criteria.
+    fn matches(&self, request: &Request) -> bool {
+        if let Some(name) = &self.name {
+            if request.name() != Some(name.as_str()) {
+                return false;
+            }
+        }
+        if let Some(filter) = &self.filter {
+            let name_matches = request.name().is_some_and(|name| filter.is_match(name));
+            if !name_matches && !filter.is_match(request.uri()) {
+                return false;
+            }
+        }
+        self.tags.is_empty() || request.tags().iter().any(|tag| self.tags.contains(tag))
+    }
+}
+
+/// Describes the criteria, for a message when nothing is selected.
+impl std::fmt::Display for Selection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut criteria = Vec::new();
+        if let Some(name) = &self.name {
+            criteria.push(format!("--name {:?}", name));
+        }
+        if let Some(line) = self.line {
+            criteria.push(format!("--line {}", line));
+        }
+        if let Some(filter) = &self.filter {
+            criteria.push(format!("--filter {:?}", filter.as_str()));
+        }
+        for tag in &self.tags {
+            criteria.push(format!("--tag {}", tag));
+        }
+        write!(f, "{}", criteria.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::HttpFileParser;
+
+    const FILE: &str = r###"@host = https://api.example.com
+
+### Login
+# @tag auth
+POST {{host}}/login
+
+{"user": "admin"}
+
+### List Users
+# @tag smoke users
+GET {{host}}/users
+
+### Create User
+# @tag users
+POST {{host}}/users
+
+###
+GET {{host}}/health
+
+### Events
+# @tag smoke
+WEBSOCKET ws://localhost:8080/events
+"###;
+
+    fn requests() -> Vec<Request> {
+        HttpFileParser::new(FILE.to_string()).parse().unwrap()
+    }
+
+    fn selected(selection: &Selection) -> Vec<String> {
+        selection
+            .select(&requests())
+            .iter()
+            .map(|request| request.name().unwrap_or(request.uri()).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_spans() {
+        let spans: Vec<(usize, usize)> = requests()
+            .iter()
+            .map(|request| (request.span().start_line, request.span().end_line))
+            .collect();
+        assert_eq!(spans, [(3, 7), (9, 11), (13, 15), (17, 18), (20, 22)]);
+    }
+
+    #[test]
+    fn test_empty_selection_selects_all() {
+        let selection = Selection::default();
+        assert!(selection.is_empty());
+        assert_eq!(selection.select(&requests()).len(), 5);
+    }
+
+    #[test]
+    fn test_select_by_name() {
+        let selection = Selection {
+            name: Some("Create User".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(selected(&selection), ["Create User"]);
+
+        let selection = Selection {
+            name: Some("create user".to_string()),
+            ..Default::default()
+        };
+        assert!(selected(&selection).is_empty());
+    }
+
+    #[test]
+    fn test_select_by_line() {
+        let at = |line| {
+            selected(&Selection {
+                line: Some(line),
+                ..Default::default()
+            })
+        };
+        assert_eq!(at(3), ["Login"]);
+        assert_eq!(at(6), ["Login"]);
+        // Blank line between two requests
+        assert_eq!(at(8), ["Login"]);
+        assert_eq!(at(11), ["List Users"]);
+        assert_eq!(at(18), ["{{host}}/health"]);
+        assert_eq!(at(100), ["Events"]);
+        // Before the first request
+        assert!(at(1).is_empty());
+    }
+
+    #[test]
+    fn test_select_by_filter() {
+        let selection = Selection {
+            filter: Some(Regex::new("users/?.*").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(selected(&selection), ["List Users", "Create User"]);
+
+        let selection = Selection {
+            filter: Some(Regex::new("^Log").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(selected(&selection), ["Login"]);
+    }
+
+    #[test]
+    fn test_select_by_tag() {
+        let selection = Selection {
+            tags: vec!["smoke".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(selected(&selection), ["List Users", "Events"]);
+
+        let selection = Selection {
+            tags: vec!["auth".to_string(), "users".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(selected(&selection), ["Login", "List Users", "Create User"]);
+    }
+
+    #[test]
+    fn test_criteria_are_combined() {
+        let selection = Selection {
+            filter: Some(Regex::new("/users").unwrap()),
+            tags: vec!["smoke".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(selected(&selection), ["List Users"]);
+        assert_eq!(selection.to_string(), "--filter \"/users\" --tag smoke");
+    }
+}
@@ -0,0 +1,577 @@
+//! The outcome of each request in a run, printed as a table at the end.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use base64::Engine;
+
+use crate::client::{HttpResponse, SentRequest};
+use crate::graphql::GraphQLResponse;
+use crate::headers::Headers;
+use crate::parser::Request;
+use crate::script::TestResult;
+use crate::timing::{format_duration, Timings};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Http,
+    WebSocket,
+    GraphQL,
+    RSocket,
+}
+
+impl Protocol {
+    pub fn of(request: &Request) -> Self {
+        match request {
+            Request::Http(_) => Protocol::Http,
+            Request::WebSocket(_) => Protocol::WebSocket,
+            Request::GraphQL(_) => Protocol::GraphQL,
+            Request::RSocket(_) => Protocol::RSocket,
+        }
+    }
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Protocol::Http => write!(f, "HTTP"),
+            Protocol::WebSocket => write!(f, "WebSocket"),
+            Protocol::GraphQL => write!(f, "GraphQL"),
+            Protocol::RSocket => write!(f, "RSocket"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// A response was received and all checks passed
+    Passed,
+    /// A response was received but these checks failed
+    Failed(Vec<TestResult>),
+    /// The request could not be sent or no response was received
+    Error(String),
+    /// Not run because an earlier request failed
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestResult {
+    /// The request name, or its method and URI
+    pub name: String,
+    pub protocol: Protocol,
+    /// Response status, for HTTP and GraphQL requests
+    pub status: Option<u16>,
+    pub duration: Option<Duration>,
+    pub outcome: Outcome,
+    /// Every check that ran, passed or not
+    pub tests: Vec<TestResult>,
+    /// The request as sent, for HTTP and GraphQL requests
+    pub request: Option<SentRequest>,
+    /// What was received, for HTTP and GraphQL requests
+    pub response: Option<ReceivedResponse>,
+    pub timings: Option<Timings>,
+    /// `# @no-log`: the request and response are kept out of reports
+    pub no_log: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceivedResponse {
+    pub headers: Headers,
+    /// The body text, or a description of a binary or saved body
+    pub body: String,
+}
+
+impl RequestResult {
+    pub fn new(request: &Request, outcome: Outcome) -> Self {
+        Self {
+            name: display_name(request),
+            protocol: Protocol::of(request),
+            status: None,
+            duration: None,
+            outcome,
+            tests: Vec::new(),
+            request: None,
+            response: None,
+            timings: None,
+            no_log: matches!(request, Request::Http(request) if request.options.no_log),
+        }
+    }
+
+    /// Records the status, timings and exchange of an HTTP response. The
+    /// exchange of a `# @no-log` request is left out.
+    pub fn with_http_response(mut self, response: &HttpResponse) -> Self {
+        if !self.no_log {
+            let body = match &response.saved_to {
+                Some(path) => format!("Response body saved to {}", path.display()),
+                None if response.body.is_binary() => response.body.summary(),
+                None => response.body.text().to_string(),
+            };
+            self.request = Some(response.request.clone());
+            self.response = Some(ReceivedResponse {
+                headers: response.headers.clone(),
+                body,
+            });
+        }
+        self.status = Some(response.status);
+        self.duration = Some(response.duration);
+        self.timings = Some(response.timings.clone());
+        self.tests = response.tests.clone();
+        self
+    }
+
+    pub fn with_graphql_response(mut self, response: &GraphQLResponse) -> Self {
+        self.status = Some(response.status);
+        self.duration = Some(response.timings.total);
+        self.request = Some(response.request.clone());
+        self.response = Some(ReceivedResponse {
+            headers: response.headers.clone(),
+            body: response.body.clone(),
+        });
+        self.timings = Some(response.timings.clone());
+        self
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Replaces each of `secrets`, as is, URL-encoded or base64-encoded, in the
+    /// exchange, checks and error with `MASK`. The values of `SECRET_HEADERS`
+    /// are masked whole.
+    pub fn mask(&mut self, secrets: &[String]) {
+        let mask = masker(secrets);
+        let mask_headers = |headers: &mut Headers| {
+            *headers = headers
+                .iter()
+                .map(|(name, value)| {
+                    if SECRET_HEADERS.iter().any(|secret| name.eq_ignore_ascii_case(secret)) {
+                        return (name.to_string(), MASK.to_string());
+                    }
+                    let mut value = value.to_string();
+                    mask(&mut value);
+                    (name.to_string(), value)
+                })
+                .collect();
+        };
+
+        if let Some(request) = &mut self.request {
+            mask(&mut request.url);
+            mask_headers(&mut request.headers);
+            if let Some(body) = &mut request.body {
+                mask(body);
+            }
+        }
+        if let Some(response) = &mut self.response {
+            mask_headers(&mut response.headers);
+            mask(&mut response.body);
+        }
+        let outcome_tests = match &mut self.outcome {
+            Outcome::Failed(tests) => Some(tests),
+            Outcome::Error(error) => {
+                mask(error);
+                None
+            }
+            _ => None,
+        };
+        for test in self.tests.iter_mut().chain(outcome_tests.into_iter().flatten()) {
+            if let Some(message) = &mut test.message {
+                mask(message);
+            }
+        }
+    }
+}
+
+/// Returns a function that replaces each of `secrets`, as is, URL-encoded or
+/// base64-encoded, in a text with `MASK`.
+fn masker(secrets: &[String]) -> impl Fn(&mut String) {
+    // Longest first, so a secret containing another is masked whole
+    let mut forms: Vec<(String, bool)> = secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .flat_map(|secret| {
+            let whole_word = secret.chars().count() < MIN_SECRET_LEN;
+            secret_forms(secret).into_iter().map(move |form| (form, whole_word))
+        })
+        .collect();
+    forms.sort_by_key(|(form, _)| std::cmp::Reverse(form.len()));
+    move |text: &mut String| {
+        for (form, whole_word) in &forms {
+            if text.contains(form.as_str()) {
+                *text = if *whole_word { replace_word(text, form) } else { text.replace(form.as_str(), MASK) };
+            }
+        }
+    }
+}
+
+/// Shown in reports in place of a secret.
+pub const MASK: &str = "******";
+
+/// Headers that carry credentials in forms a secret may not appear in as is,
+/// such as `Basic` auth or a session cookie.
+const SECRET_HEADERS: [&str; 3] = ["Authorization", "Cookie", "Set-Cookie"];
+
+/// Secrets shorter than this are only masked where they stand alone, since
+/// masking them inside other words would hide unrelated text.
+const MIN_SECRET_LEN: usize = 4;
+
+/// `secret` as is and in the encodings it may be sent in: percent-encoded in a
+/// URL, form-encoded in a body and base64-encoded.
+fn secret_forms(secret: &str) -> Vec<String> {
+    let form_encoded: String = url::form_urlencoded::byte_serialize(secret.as_bytes()).collect();
+    let mut forms = vec![
+        secret.to_string(),
+        form_encoded.replace('+', "%20"),
+        form_encoded,
+        STANDARD.encode(secret),
+        STANDARD_NO_PAD.encode(secret),
+        URL_SAFE_NO_PAD.encode(secret),
+    ];
+    forms.sort();
+    forms.dedup();
+    forms
+}
+
+/// Replaces `word` in `text` with `MASK` where it is not part of a longer run
+/// of letters and digits.
+fn replace_word(text: &str, word: &str) -> String {
+    let stands_alone = |c: Option<char>| c.is_none_or(|c| !c.is_alphanumeric());
+    let mut masked = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, _) in text.match_indices(word) {
+        let end = start + word.len();
+        if stands_alone(text[..start].chars().next_back()) && stands_alone(text[end..].chars().next()) {
+            masked.push_str(&text[last..start]);
+            masked.push_str(MASK);
+            last = end;
+        }
+    }
+    masked.push_str(&text[last..]);
+    masked
+}
+
+/// The results of running one file, in the order the requests appear in it.
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    pub file: PathBuf,
+    /// The environment the file was run in, if one was chosen
+    pub environment: Option<String>,
+    pub results: Vec<RequestResult>,
+    /// Why the file could not be run, e.g. it failed to parse
+    pub error: Option<String>,
+}
+
+impl RunSummary {
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        Self {
+            file: file.into(),
+            environment: None,
+            results: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn push(&mut self, result: RequestResult) {
+        self.results.push(result);
+    }
+
+    /// Masks `secrets` in every result and in the error of the file, before
+    /// the summary goes into a report.
+    pub fn mask(&mut self, secrets: &[String]) {
+        for result in &mut self.results {
+            result.mask(secrets);
+        }
+        if let Some(error) = &mut self.error {
+            masker(secrets)(error);
+        }
+    }
+
+    pub fn passed(&self) -> usize {
+        self.count(|outcome| *outcome == Outcome::Passed)
+    }
+
+    /// Number of requests with failed checks.
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Failed(_)))
+    }
+
+    /// Number of requests that got no response, plus one if the file could
+    /// not be run.
+    pub fn errors(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Error(_))) + usize::from(self.error.is_some())
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| *outcome == Outcome::Skipped)
+    }
+
+    /// Total time of the requests that ran.
+    pub fn duration(&self) -> Duration {
+        self.results.iter().filter_map(|result| result.duration).sum()
+    }
+
+    /// Failed checks with the name of their request, in order.
+    pub fn failed_checks(&self) -> impl Iterator<Item = (&str, &TestResult)> {
+        self.results.iter().flat_map(|result| match &result.outcome {
+            Outcome::Failed(tests) => tests.iter().map(|test| (result.name.as_str(), test)).collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    /// A table with one row per request, followed by the totals.
+    pub fn render(&self) -> String {
+        let header = ["Name", "Protocol", "Status", "Duration", "Result"].map(String::from);
+        let rows: Vec<[String; 5]> = self
+            .results
+            .iter()
+            .map(|result| {
+                [
+                    result.name.clone(),
+                    result.protocol.to_string(),
+                    result.status.map_or("-".to_string(), |status| status.to_string()),
+                    result.duration.map_or("-".to_string(), format_duration),
+                    match &result.outcome {
+                        Outcome::Passed => "passed".to_string(),
+                        Outcome::Failed(tests) => format!("failed ({} check(s))", tests.len()),
+                        Outcome::Error(_) => "error".to_string(),
+                        Outcome::Skipped => "not run".to_string(),
+                    },
+                ]
+            })
+            .collect();
+
+        let mut widths = header.clone().map(|cell| cell.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let mut table = String::new();
+        for row in std::iter::once(&header).chain(&rows) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell))
+                .collect();
+            table.push_str(cells.join("  ").trim_end());
+            table.push('\n');
+        }
+        table.push('\n');
+        table.push_str(&self.totals());
+        table
+    }
+
+    /// How many requests ended each way, such as `4 request(s): 4 passed, ...`.
+    pub fn totals(&self) -> String {
+        format!(
+            "{} request(s): {} passed, {} failed, {} error(s), {} not run",
+            self.results.len(),
+            self.passed(),
+            self.failed(),
+            self.errors(),
+            self.skipped(),
+        )
+    }
+
+    fn count(&self, predicate: impl Fn(&Outcome) -> bool) -> usize {
+        self.results.iter().filter(|result| predicate(&result.outcome)).count()
+    }
+}
+
+/// The request name, or its method and URI for unnamed requests.
+pub fn display_name(request: &Request) -> String {
+    if let Some(name) = request.name() {
+        return name.to_string();
+    }
+    match request {
+        Request::Http(request) => format!("{} {}", request.method, request.uri),
+        Request::WebSocket(request) => format!("WEBSOCKET {}", request.uri),
+        Request::GraphQL(request) => format!("GRAPHQL {}", request.uri),
+        Request::RSocket(request) => format!("RSOCKET {}", request.uri),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::HttpFileParser;
+
+    fn requests() -> Vec<Request> {
+        let content = r###"### Login
+POST https://api.example.com/login
+###
+GET https://api.example.com/users
+### Events
+WEBSOCKET ws://localhost:8080/events
+###
+GRAPHQL https://api.example.com/graphql
+
+{ users { name } }
+"###;
+        HttpFileParser::new(content.to_string()).parse().unwrap()
+    }
+
+    fn failed_check(name: &str) -> TestResult {
+        TestResult {
+            name: name.to_string(),
+            passed: false,
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_display_name() {
+        let names: Vec<String> = requests().iter().map(display_name).collect();
+        assert_eq!(
+            names,
+            ["Login", "GET https://api.example.com/users", "Events", "GRAPHQL https://api.example.com/graphql"]
+        );
+    }
+
+    #[test]
+    fn test_no_log_keeps_the_exchange_out() {
+        let content = "### Login\n# @no-log\nPOST https://api.example.com/login\n###\nGET https://api.example.com/users\n";
+        let requests = HttpFileParser::new(content.to_string()).parse().unwrap();
+        let response = HttpResponse {
+            request: SentRequest {
+                method: "POST".to_string(),
+                url: "https://api.example.com/login".to_string(),
+                headers: Headers::new(),
+                body: Some("{\"password\": \"hunter22\"}".to_string()),
+            },
+            status: 200,
+            version: reqwest::Version::HTTP_11,
+            redirects: Vec::new(),
+            saved_to: None,
+            timings: Timings::default(),
+            headers: Headers::from([("Set-Cookie", "session=abc")]),
+            body: crate::body::ResponseBody::from("token"),
+            content_type: None,
+            duration: Duration::from_millis(35),
+            tests: Vec::new(),
+            logs: Vec::new(),
+        };
+
+        let login = RequestResult::new(&requests[0], Outcome::Passed).with_http_response(&response);
+        assert!(login.no_log);
+        assert_eq!((login.status, login.duration), (Some(200), Some(Duration::from_millis(35))));
+        assert_eq!((login.request, login.response), (None, None));
+
+        let users = RequestResult::new(&requests[1], Outcome::Passed).with_http_response(&response);
+        assert!(!users.no_log);
+        assert!(users.request.is_some() && users.response.is_some());
+    }
+
+    #[test]
+    fn test_mask() {
+        let requests = requests();
+        let mut summary = RunSummary::default();
+        let mut login = RequestResult::new(&requests[0], Outcome::Failed(vec![failed_check("token")]));
+        login.request = Some(SentRequest {
+            method: "POST".to_string(),
+            url: "https://api.example.com/login?key=abcd1234".to_string(),
+            headers: Headers::from([("Authorization", "Bearer abcd1234-long"), ("X-Api-Key", "abcd1234")]),
+            body: Some("{\"password\": \"hunter22\"}".to_string()),
+        });
+        login.response = Some(ReceivedResponse {
+            headers: Headers::from([("Set-Cookie", "session=hunter22")]),
+            body: "welcome hunter22".to_string(),
+        });
+        login.tests = vec![TestResult {
+            name: "token".to_string(),
+            passed: false,
+            message: Some("expected abcd1234".to_string()),
+        }];
+        summary.push(login);
+        summary.push(RequestResult::new(&requests[1], Outcome::Error("401 for key abcd1234".to_string())));
+
+        summary.mask(&["abcd1234".to_string(), "hunter22".to_string(), "abcd1234-long".to_string()]);
+        let login = &summary.results[0];
+        let request = login.request.as_ref().unwrap();
+        assert_eq!(request.url, "https://api.example.com/login?key=******");
+        assert_eq!(request.headers.get("Authorization"), Some("******"));
+        assert_eq!(request.headers.get("X-Api-Key"), Some("******"));
+        assert_eq!(request.body.as_deref(), Some("{\"password\": \"******\"}"));
+        let response = login.response.as_ref().unwrap();
+        assert_eq!(response.headers.get("Set-Cookie"), Some("******"));
+        assert_eq!(response.body, "welcome ******");
+        assert_eq!(login.tests[0].message.as_deref(), Some("expected ******"));
+        assert_eq!(summary.results[1].outcome, Outcome::Error("401 for key ******".to_string()));
+    }
+
+    #[test]
+    fn test_mask_file_error() {
+        let mut summary = RunSummary::new("login.http");
+        summary.error = Some("Failed to connect to https://api.example.com/?key=abcd1234".to_string());
+        summary.mask(&["abcd1234".to_string()]);
+        assert_eq!(summary.error.as_deref(), Some("Failed to connect to https://api.example.com/?key=******"));
+    }
+
+    #[test]
+    fn test_mask_encoded_and_short_secrets() {
+        let requests = requests();
+        let mut result = RequestResult::new(&requests[0], Outcome::Passed);
+        result.request = Some(SentRequest {
+            method: "POST".to_string(),
+            url: "https://api.example.com/login?pass=p%40ss%20word&pin=123&page=1234".to_string(),
+            headers: Headers::from([("cookie", "id=42"), ("X-Auth", "cEBzcyB3b3Jk")]),
+            body: Some("pass=p%40ss+word&pin=123".to_string()),
+        });
+        result.response = Some(ReceivedResponse {
+            headers: Headers::new(),
+            body: "{\"pass\": \"cEBzcyB3b3Jk\", \"ids\": [123, 41234]}".to_string(),
+        });
+
+        result.mask(&["p@ss word".to_string(), "123".to_string()]);
+        let request = result.request.as_ref().unwrap();
+        assert_eq!(request.url, "https://api.example.com/login?pass=******&pin=******&page=1234");
+        assert_eq!(request.headers.get("Cookie"), Some("******"));
+        assert_eq!(request.headers.get("X-Auth"), Some("******"));
+        assert_eq!(request.body.as_deref(), Some("pass=******&pin=******"));
+        let response = result.response.as_ref().unwrap();
+        assert_eq!(response.body, "{\"pass\": \"******\", \"ids\": [******, 41234]}");
+    }
+
+    #[test]
+    fn test_render_table() {
+        let requests = requests();
+        let mut summary = RunSummary::default();
+        summary.push(
+            RequestResult::new(&requests[0], Outcome::Passed)
+                .with_status(200)
+                .with_duration(Duration::from_millis(35)),
+        );
+        summary.push(
+            RequestResult::new(&requests[1], Outcome::Failed(vec![failed_check("status == 200")]))
+                .with_status(500)
+                .with_duration(Duration::from_millis(120)),
+        );
+        summary.push(RequestResult::new(&requests[2], Outcome::Error("connection refused".to_string())));
+        summary.push(RequestResult::new(&requests[3], Outcome::Skipped));
+
+        assert_eq!(
+            summary.render(),
+            "\
+Name                                     Protocol   Status  Duration  Result
+Login                                    HTTP       200     35 ms     passed
+GET https://api.example.com/users        HTTP       500     120 ms    failed (1 check(s))
+Events                                   WebSocket  -       -         error
+GRAPHQL https://api.example.com/graphql  GraphQL    -       -         not run
+
+4 request(s): 1 passed, 1 failed, 1 error(s), 1 not run"
+        );
+        assert_eq!(summary.errors(), 1);
+        let failed: Vec<(&str, &str)> = summary
+            .failed_checks()
+            .map(|(request, test)| (request, test.name.as_str()))
+            .collect();
+        assert_eq!(failed, [("GET https://api.example.com/users", "status == 200")]);
+    }
+}
@@ -0,0 +1,317 @@
+//! Per-request timing breakdown: DNS lookup, TCP connect, TLS handshake,
+//! time to first byte and download, plus request and response sizes.
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::instrument::WithSubscriber;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    /// `None` when no lookup was made: the request reused a pooled
+    /// connection or the host is an IP address
+    pub dns: Option<Duration>,
+    /// `None` when the request reused a pooled connection
+    pub connect: Option<Duration>,
+    /// `None` for plain http and reused connections
+    pub tls: Option<Duration>,
+    /// From sending the request until the response headers arrived,
+    /// including DNS, TCP connect and TLS handshake of a new connection
+    pub ttfb: Duration,
+    /// Reading the response body
+    pub download: Duration,
+    pub total: Duration,
+    /// Request line, headers and body as sent
+    pub request_size: u64,
+    /// Status line, headers and body as received, before decompression
+    pub response_size: u64,
+}
+
+/// Names of the phases accepted by `Timings::phase`
+pub const PHASES: &[&str] = &["dns", "connect", "tls", "ttfb", "download", "total"];
+
+impl Timings {
+    /// Looks up a phase by name; phases that did not happen count as zero.
+    pub fn phase(&self, name: &str) -> Option<Duration> {
+        Some(match name {
+            "dns" => self.dns.unwrap_or_default(),
+            "connect" => self.connect.unwrap_or_default(),
+            "tls" => self.tls.unwrap_or_default(),
+            "ttfb" => self.ttfb,
+            "download" => self.download,
+            "total" => self.total,
+            _ => return None,
+        })
+    }
+
+    /// The time waiting for the response headers after the connection was made.
+    pub fn waiting(&self) -> Duration {
+        let connecting = [self.dns, self.connect, self.tls].into_iter().flatten().sum();
+        self.ttfb.saturating_sub(connecting)
+    }
+}
+
+impl std::fmt::Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let phase = |duration: Option<Duration>| duration.map(format_duration).unwrap_or_else(|| "-".to_string());
+        write!(
+            f,
+            "DNS {}, connect {}, TLS {}, TTFB {}, download {}, total {} | sent {}, received {}",
+            phase(self.dns),
+            phase(self.connect),
+            phase(self.tls),
+            format_duration(self.ttfb),
+            format_duration(self.download),
+            format_duration(self.total),
+            format_size(self.request_size),
+            format_size(self.response_size),
+        )
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_millis(10) {
+        format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{} ms", duration.as_millis())
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Time spent opening connections, summed over the connections opened since
+/// the last `ConnectionTimer::take`. `None` for phases that did not happen.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConnectionPhases {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+}
+
+/// Times the DNS lookup, TCP connect and TLS handshake of the connection a
+/// request opens. Set it as the client's resolver to time lookups, and send
+/// requests through `send` to time connecting.
+///
+/// `reqwest` has no hook around its connector, so connecting is timed from the
+/// events `hyper` emits on the request's own connection: `connecting to` and
+/// `connected to` around the TCP connect, and `handshake complete` once the
+/// connector, including any TLS handshake, returned the connection.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionTimer {
+    phases: Arc<Mutex<ConnectionPhases>>,
+}
+
+impl ConnectionTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the phases recorded since the last call.
+    pub fn take(&self) -> ConnectionPhases {
+        std::mem::take(&mut *self.phases.lock().unwrap())
+    }
+
+    /// Awaits `request`, recording the connection it opens, if any. With `tls`
+    /// the time from the TCP connect to the connection being ready counts as
+    /// the TLS handshake.
+    pub async fn send<F: Future>(&self, tls: bool, request: F) -> F::Output {
+        let events = ConnectEvents::default();
+        let output = request.with_subscriber(events.clone()).await;
+        let events = *events.0.lock().unwrap();
+        if let (Some(connecting), Some(connected), Some(ready)) = (events.connecting, events.connected, events.ready) {
+            let add = |total: Option<Duration>, duration: Duration| Some(total.unwrap_or_default() + duration);
+            let mut phases = self.phases.lock().unwrap();
+            phases.connect = add(phases.connect, connected - connecting);
+            if tls {
+                phases.tls = add(phases.tls, ready - connected);
+            }
+        }
+        output
+    }
+}
+
+impl Resolve for ConnectionTimer {
+    fn resolve(&self, name: Name) -> Resolving {
+        let phases = self.phases.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            let lookup = started.elapsed();
+            let mut phases = phases.lock().unwrap();
+            phases.dns = Some(phases.dns.unwrap_or_default() + lookup);
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// When `hyper` started and finished connecting, from its trace events.
+#[derive(Debug, Clone, Copy, Default)]
+struct ConnectInstants {
+    /// First `connecting to`; later addresses are tried after it failed
+    connecting: Option<Instant>,
+    connected: Option<Instant>,
+    ready: Option<Instant>,
+}
+
+/// Subscriber for the duration of one request, see `ConnectionTimer::send`.
+#[derive(Debug, Clone, Default)]
+struct ConnectEvents(Arc<Mutex<ConnectInstants>>);
+
+impl Subscriber for ConnectEvents {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_event() && metadata.target().starts_with("hyper::client")
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        let now = Some(Instant::now());
+        let mut instants = self.0.lock().unwrap();
+        if message.0.starts_with("connecting to ") {
+            instants.connecting = instants.connecting.or(now);
+        } else if message.0.starts_with("connected to ") {
+            instants.connected = now;
+        } else if message.0.starts_with("handshake complete") {
+            instants.ready = now;
+        }
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+/// The `message` field of an event.
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+/// Size of `request` serialized as HTTP/1.1. Streamed bodies count with the
+/// length given in `Content-Length`.
+pub fn request_size(request: &reqwest::Request) -> u64 {
+    let target = match request.url().query() {
+        Some(query) => format!("{}?{}", request.url().path(), query),
+        None => request.url().path().to_string(),
+    };
+    let line = format!("{} {} HTTP/1.1\r\n", request.method(), target).len() as u64;
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes().map(|bytes| bytes.len() as u64))
+        .or_else(|| {
+            request
+                .headers()
+                .get(reqwest::header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok()?.parse().ok())
+        })
+        .unwrap_or(0);
+    line + headers_size(request.headers()) + body
+}
+
+/// Size of the status line and headers of `response` serialized as HTTP/1.1.
+pub fn response_head_size(response: &reqwest::Response) -> u64 {
+    let line = format!("HTTP/1.1 {}\r\n", response.status()).len() as u64;
+    line + headers_size(response.headers())
+}
+
+fn headers_size(headers: &reqwest::header::HeaderMap) -> u64 {
+    let fields: usize = headers.iter().map(|(k, v)| k.as_str().len() + v.len() + 4).sum();
+    fields as u64 + 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let timings = Timings {
+            dns: Some(Duration::from_micros(1500)),
+            connect: Some(Duration::from_millis(12)),
+            tls: None,
+            ttfb: Duration::from_millis(120),
+            download: Duration::from_millis(30),
+            total: Duration::from_millis(164),
+            request_size: 312,
+            response_size: 4300,
+        };
+        assert_eq!(
+            timings.to_string(),
+            "DNS 1.5 ms, connect 12 ms, TLS -, TTFB 120 ms, download 30 ms, total 164 ms | sent 312 B, received 4.2 KB"
+        );
+        assert_eq!(timings.waiting(), Duration::from_micros(106_500));
+        assert_eq!(timings.phase("tls"), Some(Duration::ZERO));
+        assert_eq!(Timings::default().phase("dns"), Some(Duration::ZERO));
+        assert_eq!(timings.phase("ttfb"), Some(Duration::from_millis(120)));
+        assert_eq!(timings.phase("latency"), None);
+    }
+
+    #[test]
+    fn test_request_size() {
+        let client = reqwest::Client::new();
+        let request = client
+            .post("http://localhost/users?page=2")
+            .header("Content-Type", "text/plain")
+            .body("hello")
+            .build()
+            .unwrap();
+        let expected = "POST /users?page=2 HTTP/1.1\r\ncontent-type: text/plain\r\n\r\nhello".len() as u64;
+        assert_eq!(request_size(&request), expected);
+    }
+
+    #[tokio::test]
+    async fn test_connection_timer() {
+        let timer = ConnectionTimer::new();
+        assert_eq!(timer.take(), ConnectionPhases::default());
+        let addresses: Vec<SocketAddr> = timer.resolve("localhost".parse().unwrap()).await.unwrap().collect();
+        assert!(!addresses.is_empty());
+        assert!(timer.take().dns.is_some());
+        assert_eq!(timer.take(), ConnectionPhases::default());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                while stream.read(&mut buf).await.is_ok_and(|n| n > 0) {
+                    let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await;
+                }
+            }
+        });
+        let client = reqwest::Client::new();
+        timer.send(false, client.get(&url).send()).await.unwrap();
+        let phases = timer.take();
+        assert!(phases.connect.is_some());
+        assert_eq!((phases.dns, phases.tls), (None, None));
+
+        // The pooled connection is reused
+        timer.send(false, client.get(&url).send()).await.unwrap();
+        assert_eq!(timer.take(), ConnectionPhases::default());
+    }
+}
@@ -1,17 +1,28 @@
 use anyhow::{Context, Result};
 use futures_util::{SinkExt, StreamExt};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_tungstenite::{connect_async_tls_with_config, tungstenite::Message, Connector};
 use url::Url;
 use crate::env::EnvironmentManager;
 use crate::parser::WebSocketRequest;
 
 pub struct WebSocketClient {
     env_manager: EnvironmentManager,
+    tls_connector: Option<native_tls::TlsConnector>,
 }
 
 impl WebSocketClient {
     pub fn new(env_manager: EnvironmentManager) -> Self {
-        Self { env_manager }
+        Self {
+            env_manager,
+            tls_connector: None,
+        }
+    }
+
+    /// Uses `connector` for `wss://` connections, e.g. one built by
+    /// `HttpClientConfig::build_tls_connector` with a client certificate.
+    pub fn with_tls_connector(mut self, connector: native_tls::TlsConnector) -> Self {
+        self.tls_connector = Some(connector);
+        self
     }
 
     pub async fn execute_request(
@@ -31,7 +42,8 @@ impl WebSocketClient {
         println!("Connecting to WebSocket: {}", url);
 
         // Connect to WebSocket
-        let (ws_stream, _) = connect_async(url)
+        let connector = self.tls_connector.clone().map(Connector::NativeTls);
+        let (ws_stream, _) = connect_async_tls_with_config(url, None, false, connector)
             .await
             .context("Failed to connect to WebSocket")?;
 
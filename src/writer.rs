@@ -0,0 +1,527 @@
+//! Writes parsed requests back out as `.http` text. Parsing the output gives
+//! the same requests again, so `fmt` can rewrite files in a canonical form.
+
+use std::time::Duration;
+
+use crate::headers::Headers;
+use crate::parser::{
+    GraphQLRequest, HttpFile, HttpFileParser, HttpRequest, Request, RequestOptions, ScriptSource, Span, METHODS,
+};
+
+/// Writes `file` with its comments and the in-file variables of all requests
+/// first, followed by each request under a `###` separator. Variables
+/// redefined further down are written under the separator of the first
+/// request that sees them. Requests are separated by a blank line.
+pub fn write_file(file: &HttpFile) -> String {
+    let mut out = String::new();
+    write_comments(&mut out, &file.comments);
+    let variables = |index: usize| {
+        file.variables
+            .iter()
+            .filter(move |variable| variable.first_request == index)
+            .map(|variable| format!("@{} = {}\n", variable.name, variable.value))
+            .collect::<String>()
+    };
+    out.push_str(&variables(0));
+    for (index, request) in file.requests.iter().enumerate() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        let written = write_request(request);
+        match written.split_once('\n') {
+            Some((separator, rest)) if index > 0 => {
+                out.push_str(separator);
+                out.push('\n');
+                out.push_str(&variables(index));
+                out.push_str(rest);
+            }
+            _ => out.push_str(&written),
+        }
+    }
+    // Defined after the last request, so no request sees them
+    let unused = if file.requests.is_empty() { String::new() } else { variables(file.requests.len()) };
+    if !unused.is_empty() || !file.trailing_comments.is_empty() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str("###\n");
+        out.push_str(&unused);
+        write_comments(&mut out, &file.trailing_comments);
+    }
+    out
+}
+
+/// Whether parsing `written` gives back `file`, which was normalized before
+/// it was written. `fmt` leaves files alone when it doesn't.
+pub fn round_trips(file: &HttpFile, written: &str) -> bool {
+    let Ok(mut reparsed) = HttpFileParser::new(written.to_string()).parse_file() else {
+        return false;
+    };
+    normalize(&mut reparsed);
+    reparsed.diagnostics.is_empty()
+        && without_spans(&reparsed.requests) == without_spans(&file.requests)
+        && reparsed.variables == file.variables
+        && reparsed.comments == file.comments
+        && reparsed.trailing_comments == file.trailing_comments
+}
+
+/// Whether every comment line of `content` is still in `written`. Directives
+/// are left out, since they are written in a canonical form.
+pub fn keeps_comments(content: &str, written: &str) -> bool {
+    let comments = |text: &str| {
+        let mut comments: Vec<String> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with("###"))
+            .filter_map(|line| line.strip_prefix("//").or_else(|| line.strip_prefix('#')).map(|rest| (line, rest)))
+            .filter(|(_, rest)| !rest.trim_start().starts_with('@'))
+            .map(|(line, _)| line.to_string())
+            .collect();
+        comments.sort();
+        comments
+    };
+    let mut written = comments(written).into_iter().peekable();
+    comments(content).into_iter().all(|comment| {
+        while written.next_if(|line| *line < comment).is_some() {}
+        written.next_if_eq(&comment).is_some()
+    })
+}
+
+/// The requests without their spans, which change when a file is rewritten.
+fn without_spans(requests: &[Request]) -> Vec<Request> {
+    let mut requests = requests.to_vec();
+    for request in &mut requests {
+        match request {
+            Request::Http(request) => request.span = Span::default(),
+            Request::GraphQL(request) => request.span = Span::default(),
+            Request::WebSocket(request) => request.span = Span::default(),
+            Request::RSocket(request) => request.span = Span::default(),
+        }
+    }
+    requests
+}
+
+pub fn write_request(request: &Request) -> String {
+    match request {
+        Request::Http(request) => write_http(request),
+        Request::GraphQL(request) => write_graphql(request),
+        Request::WebSocket(request) => {
+            let mut out = String::new();
+            write_separator(&mut out, request.name.as_deref(), &request.tags);
+            write_comments(&mut out, &request.comments);
+            out.push_str(&format!("WEBSOCKET {}\n", request.uri));
+            write_headers(&mut out, &request.headers, &request.header_comments);
+            write_messages(&mut out, request.messages.iter().map(|m| (m.content.as_str(), m.wait_for_server, &m.comments)));
+            out
+        }
+        Request::RSocket(request) => {
+            let mut out = String::new();
+            write_separator(&mut out, request.name.as_deref(), &request.tags);
+            write_comments(&mut out, &request.comments);
+            out.push_str(&format!("RSOCKET {}\n", request.uri));
+            write_headers(&mut out, &request.headers, &request.header_comments);
+            write_messages(&mut out, request.messages.iter().map(|m| (m.content.as_str(), m.wait_for_server, &m.comments)));
+            out
+        }
+    }
+}
+
+/// Rewrites header names in the canonical casing, see `headers::canonical_name`.
+pub fn normalize(file: &mut HttpFile) {
+    for request in &mut file.requests {
+        match request {
+            Request::Http(request) => request.headers.canonicalize_names(),
+            Request::GraphQL(request) => request.headers.canonicalize_names(),
+            Request::WebSocket(request) => request.headers.canonicalize_names(),
+            Request::RSocket(request) => request.headers.canonicalize_names(),
+        }
+    }
+}
+
+fn write_http(request: &HttpRequest) -> String {
+    let mut out = String::new();
+    write_separator(&mut out, request.name.as_deref(), &request.tags);
+    write_comments(&mut out, &request.comments);
+    for directive in option_directives(&request.options) {
+        out.push_str(&format!("# @{}\n", directive));
+    }
+    for assertion in &request.assertions {
+        out.push_str(&format!("# @assert {}\n", assertion.text));
+    }
+    for script in &request.pre_request_scripts {
+        out.push_str(&write_script('<', script));
+    }
+
+    out.push_str(&format!("{} {}", request.method, request.uri));
+    if let Some(version) = &request.http_version {
+        out.push_str(&format!(" {}", version));
+    }
+    out.push('\n');
+    write_headers(&mut out, &request.headers, &request.header_comments);
+
+    if let Some(body) = &request.body {
+        out.push_str(&format!("\n{}\n", body));
+    }
+    if !request.response_handlers.is_empty() || request.output.is_some() {
+        out.push('\n');
+    }
+    for handler in &request.response_handlers {
+        out.push_str(&write_script('>', handler));
+    }
+    if let Some(output) = &request.output {
+        let marker = if output.overwrite { ">>!" } else { ">>" };
+        out.push_str(&format!("{} {}\n", marker, output.path));
+    }
+    out
+}
+
+fn write_graphql(request: &GraphQLRequest) -> String {
+    let mut out = String::new();
+    write_separator(&mut out, request.name.as_deref(), &request.tags);
+    write_comments(&mut out, &request.comments);
+    out.push_str(&format!("GRAPHQL {}\n", request.uri));
+    write_headers(&mut out, &request.headers, &request.header_comments);
+    if !request.query.is_empty() || !request.query_comments.is_empty() {
+        out.push('\n');
+        write_comments(&mut out, &request.query_comments);
+    }
+    if !request.query.is_empty() {
+        out.push_str(&format!("{}\n", request.query));
+    }
+    if let Some(variables) = &request.variables {
+        let json = serde_json::to_string_pretty(variables).unwrap_or_else(|_| variables.to_string());
+        out.push_str(&format!("\n{}\n", json));
+    }
+    out
+}
+
+/// Writes the messages of a WebSocket or RSocket request. A message that
+/// waits for server messages is preceded by one `=== wait-for-server` line per
+/// message, and its comments follow those lines.
+fn write_messages<'a>(out: &mut String, messages: impl Iterator<Item = (&'a str, usize, &'a Vec<String>)>) {
+    for (index, (content, wait_for_server, comments)) in messages.enumerate() {
+        if index == 0 {
+            out.push('\n');
+        } else if wait_for_server == 0 {
+            out.push_str("===\n");
+        }
+        for _ in 0..wait_for_server {
+            out.push_str("=== wait-for-server\n");
+        }
+        write_comments(out, comments);
+        out.push_str(content);
+        out.push('\n');
+    }
+}
+
+/// Writes the `###` line, with the request name if it has one, and the tags.
+fn write_separator(out: &mut String, name: Option<&str>, tags: &[String]) {
+    match name {
+        // A name that looks like a method would be read as the request line
+        Some(name) if name.is_empty() || METHODS.contains(&name.to_uppercase().as_str()) => {
+            out.push_str(&format!("###\n# @name {}\n", name));
+        }
+        Some(name) => out.push_str(&format!("### {}\n", name)),
+        None => out.push_str("###\n"),
+    }
+    if !tags.is_empty() {
+        out.push_str(&format!("# @tag {}\n", tags.join(" ")));
+    }
+}
+
+/// Writes the headers with each comment line after the headers before it.
+fn write_headers(out: &mut String, headers: &Headers, header_comments: &[(usize, String)]) {
+    let mut comments = header_comments.iter().peekable();
+    for (index, (name, value)) in headers.iter().enumerate() {
+        while let Some((_, comment)) = comments.next_if(|(before, _)| *before <= index) {
+            out.push_str(comment);
+            out.push('\n');
+        }
+        out.push_str(&format!("{}: {}\n", name, value));
+    }
+    for (_, comment) in comments {
+        out.push_str(comment);
+        out.push('\n');
+    }
+}
+
+fn write_comments(out: &mut String, comments: &[String]) {
+    for comment in comments {
+        out.push_str(comment);
+        out.push('\n');
+    }
+}
+
+fn write_script(marker: char, script: &ScriptSource) -> String {
+    match script {
+        ScriptSource::File(path) => format!("{} {}\n", marker, path),
+        ScriptSource::Inline(code) if !code.contains('\n') => format!("{} {{% {} %}}\n", marker, code),
+        ScriptSource::Inline(code) => format!("{} {{%\n{}\n%}}\n", marker, code),
+    }
+}
+
+/// The `# @` directives that give `options`, without the `# @` prefix.
+fn option_directives(options: &RequestOptions) -> Vec<String> {
+    let mut directives = Vec::new();
+    if options.no_cookie_jar {
+        directives.push("no-cookie-jar".to_string());
+    }
+    if options.no_redirect {
+        directives.push("no-redirect".to_string());
+    }
+    if let Some(max) = options.max_redirects {
+        directives.push(format!("max-redirects {}", max));
+    }
+    if let Some(timeout) = options.timeout {
+        directives.push(format!("timeout {}", format_timeout(timeout)));
+    }
+    if let Some(timeout) = options.connection_timeout {
+        directives.push(format!("connection-timeout {}", format_timeout(timeout)));
+    }
+    if options.no_log {
+        directives.push("no-log".to_string());
+    }
+    directives
+}
+
+/// Formats a timeout in the largest unit that keeps it exact, e.g. `2m` or `500ms`.
+fn format_timeout(timeout: Duration) -> String {
+    let millis = timeout.as_millis();
+    if millis > 0 && millis.is_multiple_of(60_000) {
+        format!("{}m", millis / 60_000)
+    } else if millis.is_multiple_of(1000) {
+        format!("{}s", millis / 1000)
+    } else {
+        format!("{}ms", millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> HttpFile {
+        let mut parser = HttpFileParser::new(content.to_string());
+        let file = parser.parse_file().unwrap();
+        assert!(file.diagnostics.is_empty(), "{:?}", file.diagnostics);
+        file
+    }
+
+
+    /// Parsing the written file gives the same file, and writing it again the same text.
+    fn assert_round_trips(content: &str) -> String {
+        let file = parse(content);
+        let written = write_file(&file);
+        let mut normalized = file.clone();
+        normalize(&mut normalized);
+        assert!(round_trips(&normalized, &write_file(&normalized)), "written:\n{}", written);
+        let reparsed = parse(&written);
+        assert_eq!(without_spans(&reparsed.requests), without_spans(&file.requests), "written:\n{}", written);
+        assert_eq!(reparsed.variables, file.variables);
+        assert_eq!(reparsed.comments, file.comments);
+        assert_eq!(write_file(&reparsed), written);
+        written
+    }
+
+    #[test]
+    fn test_http_request_round_trips() {
+        let written = assert_round_trips(
+            r###"# Users API
+@host = https://api.example.com
+
+###   Create User
+// Creates a user
+# @tag users, smoke
+# @no-redirect
+# @timeout 600
+# @assert status == 201
+< {% request.variables.set("id", $random.uuid) %}
+POST {{host}}/users HTTP/2
+Content-Type: application/json
+accept: text/html
+Accept: application/json
+
+
+{
+  "name": "John",
+    "indented": true
+}
+> {%
+    client.test("created", function () {
+        client.assert(response.status === 201);
+    });
+%}
+> ./check.js
+>>! ./created.json
+###
+# @name get
+GET {{host}}/users
+### upload
+POST {{host}}/upload
+
+< ./payload.bin
+"###,
+        );
+        assert!(written.starts_with("# Users API\n@host = https://api.example.com\n\n### Create User\n# @tag users smoke\n// Creates a user\n# @no-redirect\n# @timeout 10m\n"));
+        assert!(written.contains("accept: text/html\nAccept: application/json\n\n\n{\n  \"name\""));
+        assert!(written.contains("###\n# @name get\nGET {{host}}/users\n"));
+        assert!(written.ends_with("POST {{host}}/upload\n\n< ./payload.bin\n"));
+    }
+
+    #[test]
+    fn test_comments_and_markers_stay_in_place() {
+        let content = r###"### Script
+POST https://api.example.com/scripts
+Content-Type: text/x-shellscript
+// Sent as plain text
+# Authorization: Bearer disabled
+Accept: text/plain
+# trailing
+
+#!/bin/sh
+# not a comment, part of the body
+>> not output either
+echo done
+
+>> ./result.txt
+"###;
+        let written = assert_round_trips(content);
+        assert_eq!(written, content);
+    }
+
+    #[test]
+    fn test_redefined_variables_stay_with_their_request() {
+        let written = assert_round_trips(
+            r###"@host = https://one.example.com
+### First
+GET {{host}}/users
+### Second
+@host = https://two.example.com
+# @name second
+GET {{host}}/users
+###
+@unused = 1
+"###,
+        );
+        assert_eq!(
+            written,
+            "@host = https://one.example.com\n\n### First\nGET {{host}}/users\n\n### second\n@host = https://two.example.com\nGET {{host}}/users\n\n###\n@unused = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_other_requests_round_trip() {
+        assert_round_trips(
+            r###"### Query
+GRAPHQL http://localhost:8080/graphql
+Authorization: Bearer token
+
+query ($id: ID!) {
+  user(id: $id) { name }
+}
+
+{
+  "id": "123",
+  "filter": {
+    "active": true
+  }
+}
+### Chat
+# @tag ws
+WEBSOCKET ws://localhost:8080/ws
+Content-Type: application/json
+
+=== wait-for-server
+{"message": "first"}
+===
+{"message": "second"}
+
+=== wait-for-server
+=== wait-for-server
+{"message": "third"}
+###
+RSOCKET ws://localhost:7878/rsocket
+
+{"route": "users"}
+"###,
+        );
+    }
+
+    #[test]
+    fn test_comments_in_other_requests_stay() {
+        let content = r###"### Query
+# explain the request
+GRAPHQL http://localhost:8080/graphql
+# auth comment
+Authorization: Bearer token
+
+# explain query
+query { users { name } }
+###
+// ws comment
+WEBSOCKET ws://localhost:8080/ws
+Content-Type: application/json
+// after the headers
+
+=== wait-for-server
+// first message
+{"message": "first"}
+===
+# second message
+{"message": "second"}
+###
+RSOCKET ws://localhost:7878/rsocket
+# no messages
+"###;
+        let written = assert_round_trips(content);
+        assert_eq!(written, content.replace("}\n###", "}\n\n###"));
+    }
+
+    #[test]
+    fn test_comments_under_separators_without_requests_stay() {
+        let written = assert_round_trips(
+            r###"### First
+GET https://example.com/one
+###
+# orphan block
+// of two lines
+###
+GET https://example.com/two
+###
+@unused = 1
+# at the end
+"###,
+        );
+        assert_eq!(
+            written,
+            "### First\nGET https://example.com/one\n\n###\n# orphan block\n// of two lines\nGET https://example.com/two\n\n###\n@unused = 1\n# at the end\n"
+        );
+        assert_round_trips("# top\n###\n# only a comment\n");
+    }
+
+    #[test]
+    fn test_keeps_comments() {
+        let content = "# top\n###\n# @tag a, b\n// note\nGET https://example.com\n# note\n";
+        assert!(keeps_comments(content, "# top\n###\n# @tag a b\n// note\n# note\nGET https://example.com\n"));
+        assert!(!keeps_comments(content, "# top\n###\n# @tag a b\nGET https://example.com\n# note\n"));
+        assert!(!keeps_comments("# twice\n# twice\n", "# twice\n"));
+    }
+
+    #[test]
+    fn test_normalize_header_names() {
+        let mut file = parse("###\nGET https://example.com\ncontent-type: text/plain\nX-Request-ID: 1\n");
+        normalize(&mut file);
+        assert_eq!(
+            write_file(&file),
+            "###\nGET https://example.com\nContent-Type: text/plain\nX-Request-ID: 1\n"
+        );
+    }
+
+    #[test]
+    fn test_format_timeout() {
+        assert_eq!(format_timeout(Duration::from_secs(120)), "2m");
+        assert_eq!(format_timeout(Duration::from_secs(90)), "90s");
+        assert_eq!(format_timeout(Duration::from_millis(1500)), "1500ms");
+        assert_eq!(format_timeout(Duration::ZERO), "0s");
+    }
+}